@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE participants;
+DROP TABLE conversations;
\ No newline at end of file
@@ -0,0 +1,20 @@
+-- Your SQL goes here
+CREATE TABLE conversations (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  chat_type TEXT NOT NULL,
+  owner_id TEXT NOT NULL,
+  group_id TEXT NOT NULL,
+  name TEXT NOT NULL,
+  avatar BIGINT,
+  metadata BLOB
+);
+CREATE TABLE participants (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  conversation_id INTEGER NOT NULL,
+  sender_id TEXT NOT NULL,
+  display_name TEXT NOT NULL,
+  joined_at BIGINT,
+  left_at BIGINT
+);
+CREATE UNIQUE INDEX "conversations_idx" ON "conversations" ("chat_type", "owner_id", "group_id");
+CREATE UNIQUE INDEX "participants_idx" ON "participants" ("conversation_id", "sender_id");
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE sender_aliases;
+DROP TABLE senders;
\ No newline at end of file
@@ -0,0 +1,17 @@
+-- Your SQL goes here
+CREATE TABLE senders (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  chat_type TEXT NOT NULL,
+  sender_id TEXT NOT NULL,
+  name TEXT NOT NULL,
+  timestamp BIGINT NOT NULL
+);
+CREATE TABLE sender_aliases (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  chat_type TEXT NOT NULL,
+  sender_id TEXT NOT NULL,
+  name TEXT NOT NULL,
+  timestamp BIGINT NOT NULL
+);
+CREATE UNIQUE INDEX "senders_idx" ON "senders" ("chat_type", "sender_id");
+CREATE INDEX "sender_aliases_idx" ON "sender_aliases" ("chat_type", "sender_id", "timestamp");
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE person_identities;
+DROP TABLE persons;
\ No newline at end of file
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE persons (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  name TEXT NOT NULL,
+  metadata BLOB
+);
+CREATE TABLE person_identities (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  person_id INTEGER NOT NULL,
+  chat_type TEXT NOT NULL,
+  sender_id TEXT NOT NULL
+);
+CREATE UNIQUE INDEX "person_identities_idx" ON "person_identities" ("chat_type", "sender_id");
+CREATE INDEX "person_identities_person_idx" ON "person_identities" ("person_id");
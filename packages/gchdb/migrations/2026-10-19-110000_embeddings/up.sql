@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE embeddings (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  record_id INTEGER NOT NULL,
+  vector BLOB NOT NULL
+);
+CREATE UNIQUE INDEX "embeddings_record_idx" ON "embeddings" ("record_id");
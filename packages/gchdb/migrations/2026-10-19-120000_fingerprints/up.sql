@@ -0,0 +1,15 @@
+-- Your SQL goes here
+CREATE TABLE fingerprints (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  record_id INTEGER NOT NULL,
+  simhash BIGINT NOT NULL,
+  band0 INTEGER NOT NULL,
+  band1 INTEGER NOT NULL,
+  band2 INTEGER NOT NULL,
+  band3 INTEGER NOT NULL
+);
+CREATE UNIQUE INDEX "fingerprints_record_idx" ON "fingerprints" ("record_id");
+CREATE INDEX "fingerprints_band0_idx" ON "fingerprints" ("band0");
+CREATE INDEX "fingerprints_band1_idx" ON "fingerprints" ("band1");
+CREATE INDEX "fingerprints_band2_idx" ON "fingerprints" ("band2");
+CREATE INDEX "fingerprints_band3_idx" ON "fingerprints" ("band3");
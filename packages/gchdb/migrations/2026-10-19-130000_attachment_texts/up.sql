@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE attachment_texts (
+  hash BIGINT NOT NULL,
+  name TEXT NOT NULL,
+  text TEXT,
+  PRIMARY KEY (hash, name)
+);
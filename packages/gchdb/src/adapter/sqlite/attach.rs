@@ -34,13 +34,22 @@ fn remove_attach(conn: &mut SqliteConnection, attach: &Attachment) -> ChatRecord
         .execute(conn)?)
 }
 
-fn get_attachs(conn: &mut SqliteConnection, record_id: i32) -> ChatRecordResult<Vec<Attachment>> {
+/// attachments of the record
+pub fn get_attachs(
+    conn: &mut SqliteConnection,
+    record_id: i32,
+) -> ChatRecordResult<Vec<Attachment>> {
     use schema::attachments::dsl;
     Ok(dsl::attachments
         .filter(dsl::record_id.eq(record_id))
         .load(conn)?)
 }
 
+/// all attachments of all records
+pub fn list_attachs(conn: &mut SqliteConnection) -> ChatRecordResult<Vec<Attachment>> {
+    Ok(attachments::table.load(conn)?)
+}
+
 pub fn remove_attachs(conn: &mut SqliteConnection, record_id: i32) -> ChatRecordResult<bool> {
     let attachs = get_attachs(conn, record_id)?;
     Ok(attachs
@@ -61,14 +70,13 @@ pub fn insert_or_update_attach_inner(
     }? == 1)
 }
 
-pub fn insert_or_update_attach(
+fn insert_or_update_attach(
     conn: &mut SqliteConnection,
-    data: Vec<u8>,
+    blob: &Blob,
     name: String,
     record_id: i32,
 ) -> ChatRecordResult<bool> {
-    let blob = Blob::new(data);
-    if insert_blob(conn, &blob)? {
+    if insert_blob(conn, blob)? {
         Ok(insert_or_update_attach_inner(
             conn,
             &Attachment::new(blob.hash, name, record_id),
@@ -77,3 +85,112 @@ pub fn insert_or_update_attach(
         Ok(false)
     }
 }
+
+/// the cached text extracted from the data by the extractor of the name,
+/// `Some(None)` if the extraction failed before
+pub fn get_attach_text(
+    conn: &mut SqliteConnection,
+    blob_hash: i64,
+    attach_name: &str,
+) -> ChatRecordResult<Option<Option<String>>> {
+    use schema::attachment_texts::dsl::*;
+    Ok(attachment_texts
+        .filter(hash.eq(blob_hash).and(name.eq(attach_name)))
+        .select(text)
+        .get_result(conn)
+        .optional()?)
+}
+
+pub fn insert_attach_text(
+    conn: &mut SqliteConnection,
+    blob_hash: i64,
+    attach_name: &str,
+    attach_text: Option<&str>,
+) -> ChatRecordResult<bool> {
+    use schema::attachment_texts::dsl::*;
+    Ok(replace_into(attachment_texts)
+        .values((
+            hash.eq(blob_hash),
+            name.eq(attach_name),
+            text.eq(attach_text),
+        ))
+        .execute(conn)?
+        == 1)
+}
+
+pub fn clear_attach_texts(conn: &mut SqliteConnection) -> ChatRecordResult<usize> {
+    Ok(delete(attachment_texts::table).execute(conn)?)
+}
+
+/// store the attachments of the record, merged with the stored attachments by the policy
+pub fn merge_attachs(
+    conn: &mut SqliteConnection,
+    record_id: i32,
+    attachs: &Attachments,
+    policy: AttachmentPolicy,
+) -> ChatRecordResult<AttachmentReport> {
+    let stored = get_attachs(conn, record_id)?
+        .into_iter()
+        .map(|attach| (attach.name.clone(), attach))
+        .collect::<HashMap<_, _>>();
+    let mut report = AttachmentReport::default();
+    for (name, data) in attachs {
+        let blob = Blob::new(data.clone());
+        // the name the data is written to and whether it replaces a stored attachment
+        let (name, replaced) = match stored.get(name) {
+            None => (name.clone(), false),
+            Some(attach) if attach.hash == blob.hash => {
+                report.unchanged.push(name.clone());
+                continue;
+            }
+            Some(_) => match policy {
+                AttachmentPolicy::ReplaceAll | AttachmentPolicy::MergeByName => {
+                    (name.clone(), true)
+                }
+                AttachmentPolicy::KeepExisting => {
+                    report.unchanged.push(name.clone());
+                    continue;
+                }
+                AttachmentPolicy::AppendVersioned => {
+                    // the first version which is free or already holds the data
+                    let name = (1..)
+                        .map(|version| AttachmentPolicy::versioned_name(name, version))
+                        .find(|name| match stored.get(name) {
+                            Some(attach) => attach.hash == blob.hash,
+                            None => !attachs.contains_key(name),
+                        })
+                        .unwrap_or_default();
+                    if stored.contains_key(&name) {
+                        report.unchanged.push(name);
+                        continue;
+                    }
+                    (name, false)
+                }
+            },
+        };
+        if !insert_or_update_attach(conn, &blob, name.clone(), record_id)? {
+            report.failed.push(name);
+        } else if replaced {
+            report.replaced.push(name);
+        } else {
+            report.added.push(name);
+        }
+    }
+    if policy == AttachmentPolicy::ReplaceAll {
+        for (name, attach) in stored {
+            if !attachs.contains_key(&name) && remove_attach(conn, &attach)? == 1 {
+                report.removed.push(name);
+            }
+        }
+    }
+    for names in [
+        &mut report.added,
+        &mut report.replaced,
+        &mut report.unchanged,
+        &mut report.removed,
+        &mut report.failed,
+    ] {
+        names.sort();
+    }
+    Ok(report)
+}
@@ -26,8 +26,3 @@ pub fn get_blob(conn: &mut SqliteConnection, blob_hash: i64) -> ChatRecordResult
         .select(blob)
         .get_result::<Vec<u8>>(conn)?)
 }
-
-pub fn remove_blob(conn: &mut SqliteConnection, hash: i64) -> ChatRecordResult<usize> {
-    use schema::blobs::{dsl, table};
-    Ok(delete(table).filter(dsl::hash.eq(hash)).execute(conn)?)
-}
@@ -0,0 +1,291 @@
+use super::*;
+use std::collections::BTreeMap;
+
+fn check_conversation(
+    conn: &mut SqliteConnection,
+    conversation: &Conversation,
+) -> ChatRecordResult<bool> {
+    use schema::conversations::dsl::*;
+    Ok(select(exists(
+        conversations.filter(
+            chat_type
+                .eq(&conversation.chat_type)
+                .and(owner_id.eq(&conversation.owner_id))
+                .and(group_id.eq(&conversation.group_id)),
+        ),
+    ))
+    .get_result(conn)
+    .unwrap_or(false))
+}
+
+fn update_conversation(
+    conn: &mut SqliteConnection,
+    conversation: &Conversation,
+) -> ChatRecordResult<usize> {
+    use schema::conversations::dsl::*;
+    Ok(update(
+        conversations.filter(
+            chat_type
+                .eq(&conversation.chat_type)
+                .and(owner_id.eq(&conversation.owner_id))
+                .and(group_id.eq(&conversation.group_id)),
+        ),
+    )
+    .set((
+        name.eq(&conversation.name),
+        avatar.eq(&conversation.avatar),
+        metadata.eq(&conversation.metadata),
+    ))
+    .execute(conn)?)
+}
+
+fn insert_conversation(
+    conn: &mut SqliteConnection,
+    conversation: &Conversation,
+) -> ChatRecordResult<usize> {
+    Ok(insert_into(conversations::table)
+        .values(conversation)
+        .execute(conn)?)
+}
+
+pub fn insert_or_update_conversation(
+    conn: &mut SqliteConnection,
+    conversation: &Conversation,
+) -> ChatRecordResult<bool> {
+    Ok(if check_conversation(conn, conversation)? {
+        update_conversation(conn, conversation)
+    } else {
+        insert_conversation(conn, conversation)
+    }? == 1)
+}
+
+pub fn get_conversation(
+    conn: &mut SqliteConnection,
+    conversation_chat_type: &str,
+    conversation_owner_id: &str,
+    conversation_group_id: &str,
+) -> ChatRecordResult<Option<Conversation>> {
+    use schema::conversations::dsl::*;
+    Ok(conversations
+        .filter(
+            chat_type
+                .eq(conversation_chat_type)
+                .and(owner_id.eq(conversation_owner_id))
+                .and(group_id.eq(conversation_group_id)),
+        )
+        .get_result(conn)
+        .optional()?)
+}
+
+fn get_conversations(
+    conn: &mut SqliteConnection,
+    conversation_owner_id: &str,
+) -> ChatRecordResult<Vec<Conversation>> {
+    use schema::conversations::dsl::*;
+    Ok(conversations
+        .filter(owner_id.eq(conversation_owner_id))
+        .load(conn)?)
+}
+
+pub fn remove_conversation(
+    conn: &mut SqliteConnection,
+    conversation: &Conversation,
+) -> ChatRecordResult<usize> {
+    use schema::conversations::{dsl::*, table};
+    let conversation_id = match conversation.id {
+        Some(conversation_id) => conversation_id,
+        None => match get_conversation(
+            conn,
+            &conversation.chat_type,
+            &conversation.owner_id,
+            &conversation.group_id,
+        )? {
+            Some(conversation) => conversation.get_id(),
+            None => return Ok(0),
+        },
+    };
+    delete(participants::table)
+        .filter(participants::conversation_id.eq(conversation_id))
+        .execute(conn)?;
+    Ok(delete(table).filter(id.eq(conversation_id)).execute(conn)?)
+}
+
+fn check_participant(
+    conn: &mut SqliteConnection,
+    participant: &Participant,
+) -> ChatRecordResult<bool> {
+    use schema::participants::dsl::*;
+    Ok(select(exists(
+        participants.filter(
+            conversation_id
+                .eq(participant.conversation_id)
+                .and(sender_id.eq(&participant.sender_id)),
+        ),
+    ))
+    .get_result(conn)
+    .unwrap_or(false))
+}
+
+fn update_participant(
+    conn: &mut SqliteConnection,
+    participant: &Participant,
+) -> ChatRecordResult<usize> {
+    use schema::participants::dsl::*;
+    Ok(update(
+        participants.filter(
+            conversation_id
+                .eq(participant.conversation_id)
+                .and(sender_id.eq(&participant.sender_id)),
+        ),
+    )
+    .set((
+        display_name.eq(&participant.display_name),
+        joined_at.eq(&participant.joined_at),
+        left_at.eq(&participant.left_at),
+    ))
+    .execute(conn)?)
+}
+
+fn insert_participant(
+    conn: &mut SqliteConnection,
+    participant: &Participant,
+) -> ChatRecordResult<usize> {
+    Ok(insert_into(participants::table)
+        .values(participant)
+        .execute(conn)?)
+}
+
+pub fn insert_or_update_participant(
+    conn: &mut SqliteConnection,
+    participant: &Participant,
+) -> ChatRecordResult<bool> {
+    Ok(if check_participant(conn, participant)? {
+        update_participant(conn, participant)
+    } else {
+        insert_participant(conn, participant)
+    }? == 1)
+}
+
+pub fn get_participants(
+    conn: &mut SqliteConnection,
+    participant_conversation_id: i32,
+) -> ChatRecordResult<Vec<Participant>> {
+    use schema::participants::dsl::*;
+    Ok(participants
+        .filter(conversation_id.eq(participant_conversation_id))
+        .load(conn)?)
+}
+
+pub fn remove_participant(
+    conn: &mut SqliteConnection,
+    participant: &Participant,
+) -> ChatRecordResult<usize> {
+    use schema::participants::{dsl::*, table};
+    Ok(delete(table)
+        .filter(
+            id.eq(participant.id).or(conversation_id
+                .eq(participant.conversation_id)
+                .and(sender_id.eq(&participant.sender_id))),
+        )
+        .execute(conn)?)
+}
+
+pub fn list_conversations(
+    conn: &mut SqliteConnection,
+    conversation_owner_id: &str,
+) -> ChatRecordResult<Vec<ConversationSummary>> {
+    use diesel::dsl::count_star;
+    use schema::records::dsl::*;
+    let mut summaries = records
+        .filter(owner_id.eq(conversation_owner_id))
+        .group_by((chat_type, group_id))
+        .select((
+            chat_type,
+            group_id,
+            count_star(),
+            diesel::dsl::max(timestamp),
+        ))
+        .load::<(String, String, i64, Option<i64>)>(conn)?
+        .into_iter()
+        .map(|(record_chat_type, record_group_id, count, last)| {
+            (
+                (record_chat_type.clone(), record_group_id.clone()),
+                ConversationSummary {
+                    chat_type: record_chat_type,
+                    owner_id: conversation_owner_id.into(),
+                    group_id: record_group_id,
+                    conversation: None,
+                    message_count: count,
+                    last_message: last,
+                },
+            )
+        })
+        .collect::<BTreeMap<_, _>>();
+    for conversation in get_conversations(conn, conversation_owner_id)? {
+        let key = (
+            conversation.chat_type.clone(),
+            conversation.group_id.clone(),
+        );
+        summaries
+            .entry(key.clone())
+            .or_insert_with(|| ConversationSummary {
+                chat_type: key.0,
+                owner_id: conversation_owner_id.into(),
+                group_id: key.1,
+                ..Default::default()
+            })
+            .conversation = Some(conversation);
+    }
+    let mut summaries = summaries.into_values().collect::<Vec<_>>();
+    summaries.sort_by_key(|summary| std::cmp::Reverse(summary.last_message));
+    Ok(summaries)
+}
+
+#[test]
+fn test_conversation() {
+    let mut recorder = test_recorder("conversation");
+    let conversation = Conversation {
+        chat_type: "test_conversation".into(),
+        owner_id: "owner".into(),
+        group_id: "group".into(),
+        name: "测试群".into(),
+        ..Default::default()
+    };
+    assert!(recorder
+        .insert_or_update_conversation(&conversation, Some(vec![0, 1, 2, 3]))
+        .unwrap());
+    let stored = recorder
+        .get_conversation("test_conversation", "owner", "group")
+        .unwrap()
+        .unwrap();
+    assert_eq!(stored.name, "测试群");
+    assert_eq!(
+        recorder.get_blob(stored.avatar.unwrap()).unwrap(),
+        vec![0, 1, 2, 3]
+    );
+    let participant = Participant {
+        conversation_id: stored.get_id(),
+        sender_id: "sender".into(),
+        display_name: "群友".into(),
+        joined_at: Some(1),
+        ..Default::default()
+    };
+    assert!(recorder.insert_or_update_participant(&participant).unwrap());
+    assert_eq!(recorder.get_participants(stored.get_id()).unwrap().len(), 1);
+    let record = Record {
+        sender_name: "群友".into(),
+        ..test_record("test_conversation", "hello", 1)
+    };
+    assert!(recorder.insert_or_update_record(&record, None).unwrap());
+    let summaries = recorder.list_conversations("owner").unwrap();
+    assert_eq!(summaries.len(), 1);
+    assert_eq!(summaries[0].message_count, 1);
+    assert_eq!(summaries[0].last_message, Some(1));
+    assert_eq!(summaries[0].conversation.as_ref().unwrap().name, "测试群");
+    assert!(recorder.remove_conversation(&stored).unwrap());
+    assert!(recorder
+        .get_participants(stored.get_id())
+        .unwrap()
+        .is_empty());
+    assert!(recorder.remove_record(&record).unwrap());
+}
@@ -0,0 +1,54 @@
+use super::*;
+
+fn check_embedding(conn: &mut SqliteConnection, embedding: &Embedding) -> ChatRecordResult<bool> {
+    use schema::embeddings::dsl::*;
+    Ok(
+        select(exists(embeddings.filter(record_id.eq(embedding.record_id))))
+            .get_result(conn)
+            .unwrap_or(false),
+    )
+}
+
+fn update_embedding(conn: &mut SqliteConnection, embedding: &Embedding) -> ChatRecordResult<usize> {
+    use schema::embeddings::dsl::*;
+    Ok(update(embeddings.filter(record_id.eq(embedding.record_id)))
+        .set(vector.eq(&embedding.vector))
+        .execute(conn)?)
+}
+
+fn insert_embedding(conn: &mut SqliteConnection, embedding: &Embedding) -> ChatRecordResult<usize> {
+    Ok(insert_into(embeddings::table)
+        .values(embedding)
+        .execute(conn)?)
+}
+
+pub fn insert_or_update_embedding(
+    conn: &mut SqliteConnection,
+    embedding: &Embedding,
+) -> ChatRecordResult<bool> {
+    Ok(if check_embedding(conn, embedding)? {
+        update_embedding(conn, embedding)
+    } else {
+        insert_embedding(conn, embedding)
+    }? == 1)
+}
+
+pub fn get_embedding(
+    conn: &mut SqliteConnection,
+    record: i32,
+) -> ChatRecordResult<Option<Embedding>> {
+    use schema::embeddings::dsl::*;
+    Ok(embeddings
+        .filter(record_id.eq(record))
+        .get_result(conn)
+        .optional()?)
+}
+
+pub fn get_embeddings(conn: &mut SqliteConnection) -> ChatRecordResult<Vec<Embedding>> {
+    Ok(embeddings::table.load(conn)?)
+}
+
+pub fn remove_embedding(conn: &mut SqliteConnection, record: i32) -> ChatRecordResult<usize> {
+    use schema::embeddings::{dsl::*, table};
+    Ok(delete(table).filter(record_id.eq(record)).execute(conn)?)
+}
@@ -0,0 +1,92 @@
+use super::*;
+use diesel::{
+    dsl::{not, sql},
+    sql_types::Bool,
+};
+
+/// compile the expression to a sql predicate, keyword leaves are searched by the indexer first
+pub fn compile_expr<'a>(
+    expr: &'a QueryExpr,
+    indexer: &ContentIndexer,
+) -> ChatRecordResult<RecordPredicate<'a>> {
+    use schema::records::dsl::*;
+    Ok(match expr {
+        QueryExpr::And(exprs) => {
+            let mut predicate: RecordPredicate = Box::new(sql::<Bool>("1"));
+            for expr in exprs {
+                predicate = Box::new(predicate.and(compile_expr(expr, indexer)?));
+            }
+            predicate
+        }
+        QueryExpr::Or(exprs) => {
+            let mut predicate: RecordPredicate = Box::new(sql::<Bool>("0"));
+            for expr in exprs {
+                predicate = Box::new(predicate.or(compile_expr(expr, indexer)?));
+            }
+            predicate
+        }
+        QueryExpr::Not(expr) => Box::new(not(compile_expr(expr, indexer)?)),
+        QueryExpr::ChatType(filter) => string_filter(chat_type, filter),
+        QueryExpr::OwnerId(filter) => string_filter(owner_id, filter),
+        QueryExpr::GroupId(filter) => string_filter(group_id, filter),
+        QueryExpr::SenderId(filter) => string_filter(sender_id, filter),
+        QueryExpr::SenderName(filter) => string_filter(sender_name, filter),
+        QueryExpr::Person(person_id) => person_filter(*person_id),
+        QueryExpr::Ids(ids) => ids_filter(ids),
+        QueryExpr::HasAttachments => attachment_filter(),
+        QueryExpr::Metadata(filter) => metadata_filter(filter),
+        QueryExpr::Before(before) => Box::new(timestamp.le(*before)),
+        QueryExpr::After(after) => Box::new(timestamp.ge(*after)),
+        QueryExpr::Keyword(keyword) => ids_filter(&indexer.search_all(keyword)?),
+    })
+}
+
+#[test]
+fn test_query_expr() {
+    let mut recorder = test_recorder("expr");
+    for (i, (sender, group, content)) in [
+        ("a", "g1", "今天天气很好"),
+        ("b", "g1", "明天天气不好"),
+        ("c", "g1", "今天天气很好"),
+        ("a", "g2", "今天天气很好"),
+    ]
+    .iter()
+    .enumerate()
+    {
+        assert!(recorder
+            .insert_or_update_record(
+                &Record {
+                    group_id: group.to_string(),
+                    sender_id: sender.to_string(),
+                    sender_name: sender.to_string(),
+                    ..test_record("test_expr", content, i as i64)
+                },
+                None
+            )
+            .unwrap());
+    }
+    recorder.refresh_index().unwrap();
+    let expr = (QueryExpr::SenderId("a".into()) | QueryExpr::SenderId("b".into()))
+        & QueryExpr::GroupId("g1".into())
+        & QueryExpr::Keyword("天气".into())
+        & !QueryExpr::Before(0);
+    assert_eq!(
+        recorder
+            .get_record_by_expr(&expr, QuerySort::TimestampAsc, 0, 10)
+            .unwrap()
+            .iter()
+            .map(|record| record.sender_id.as_str())
+            .collect::<Vec<_>>(),
+        vec!["b"]
+    );
+    let expr = QueryExpr::ChatType("test_expr".into()) & !QueryExpr::Keyword("明天".into());
+    assert_eq!(
+        recorder
+            .get_record_by_expr(&expr, QuerySort::TimestampDesc, 1, 10)
+            .unwrap()
+            .iter()
+            .map(|record| record.timestamp)
+            .collect::<Vec<_>>(),
+        vec![2, 0]
+    );
+}
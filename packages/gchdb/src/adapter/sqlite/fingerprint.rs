@@ -0,0 +1,109 @@
+use super::*;
+
+fn check_fingerprint(
+    conn: &mut SqliteConnection,
+    fingerprint: &Fingerprint,
+) -> ChatRecordResult<bool> {
+    use schema::fingerprints::dsl::*;
+    Ok(select(exists(
+        fingerprints.filter(record_id.eq(fingerprint.record_id)),
+    ))
+    .get_result(conn)
+    .unwrap_or(false))
+}
+
+fn update_fingerprint(
+    conn: &mut SqliteConnection,
+    fingerprint: &Fingerprint,
+) -> ChatRecordResult<usize> {
+    use schema::fingerprints::dsl::*;
+    Ok(
+        update(fingerprints.filter(record_id.eq(fingerprint.record_id)))
+            .set((
+                simhash.eq(fingerprint.simhash),
+                band0.eq(fingerprint.band0),
+                band1.eq(fingerprint.band1),
+                band2.eq(fingerprint.band2),
+                band3.eq(fingerprint.band3),
+            ))
+            .execute(conn)?,
+    )
+}
+
+fn insert_fingerprint(
+    conn: &mut SqliteConnection,
+    fingerprint: &Fingerprint,
+) -> ChatRecordResult<usize> {
+    Ok(insert_into(fingerprints::table)
+        .values(fingerprint)
+        .execute(conn)?)
+}
+
+pub fn insert_or_update_fingerprint(
+    conn: &mut SqliteConnection,
+    fingerprint: &Fingerprint,
+) -> ChatRecordResult<bool> {
+    Ok(if check_fingerprint(conn, fingerprint)? {
+        update_fingerprint(conn, fingerprint)
+    } else {
+        insert_fingerprint(conn, fingerprint)
+    }? == 1)
+}
+
+pub fn get_fingerprint(
+    conn: &mut SqliteConnection,
+    record: i32,
+) -> ChatRecordResult<Option<Fingerprint>> {
+    use schema::fingerprints::dsl::*;
+    Ok(fingerprints
+        .filter(record_id.eq(record))
+        .get_result(conn)
+        .optional()?)
+}
+
+/// record ids of all fingerprinted records
+pub fn get_fingerprinted(conn: &mut SqliteConnection) -> ChatRecordResult<Vec<i32>> {
+    use schema::fingerprints::dsl::*;
+    Ok(fingerprints.select(record_id).load(conn)?)
+}
+
+/// fingerprints sharing a band with the fingerprint, the fingerprint itself included if it is stored
+pub fn get_fingerprint_candidates(
+    conn: &mut SqliteConnection,
+    fingerprint: &Fingerprint,
+) -> ChatRecordResult<Vec<Fingerprint>> {
+    use schema::fingerprints::dsl::*;
+    Ok(fingerprints
+        .filter(
+            band0
+                .eq(fingerprint.band0)
+                .or(band1.eq(fingerprint.band1))
+                .or(band2.eq(fingerprint.band2))
+                .or(band3.eq(fingerprint.band3)),
+        )
+        .load(conn)?)
+}
+
+pub fn get_fingerprints(conn: &mut SqliteConnection) -> ChatRecordResult<Vec<Fingerprint>> {
+    Ok(fingerprints::table.load(conn)?)
+}
+
+pub fn remove_fingerprint(conn: &mut SqliteConnection, record: i32) -> ChatRecordResult<usize> {
+    use schema::fingerprints::{dsl::*, table};
+    Ok(delete(table).filter(record_id.eq(record)).execute(conn)?)
+}
+
+/// store the fingerprint of the content, the stale fingerprint is removed if the content is too short
+pub fn fingerprint_record(
+    conn: &mut SqliteConnection,
+    record_id: i32,
+    content: &str,
+) -> ChatRecordResult<bool> {
+    Ok(match Fingerprint::new(record_id, content) {
+        Some(fingerprint) => insert_or_update_fingerprint(conn, &fingerprint)?,
+        None => {
+            remove_fingerprint(conn, record_id)?;
+            false
+        }
+    })
+}
@@ -0,0 +1,172 @@
+use super::*;
+use diesel::{dsl::sql, sql_types::Bool};
+use std::collections::VecDeque;
+
+const BATCH_SIZE: i64 = 1000;
+
+/// streams records in `(timestamp, id)` order, loading one keyset batch at a time,
+/// the offset, limit, cursor and sort of the query are ignored
+pub struct RecordIter {
+    conn: Pool<ConnectionManager<SqliteConnection>>,
+    query: Option<Query>,
+    /// keyword results of the indexer, resolved once into `ordered` by the first batch
+    hits: Option<Vec<i32>>,
+    /// ids of the records matched by the query and the keyword, in `(timestamp, id)` order
+    ordered: Option<VecDeque<i32>>,
+    cursor: Cursor,
+    batch: VecDeque<Record>,
+    batch_size: i64,
+    done: bool,
+}
+
+impl RecordIter {
+    /// all records, without the default time scope of a query
+    pub(crate) fn all(conn: Pool<ConnectionManager<SqliteConnection>>) -> Self {
+        Self {
+            conn,
+            query: None,
+            hits: None,
+            ordered: None,
+            cursor: Cursor::oldest(),
+            batch: VecDeque::new(),
+            batch_size: BATCH_SIZE,
+            done: false,
+        }
+    }
+
+    /// records matched by the query, `hits` are the keyword results of the indexer
+    pub(crate) fn new(
+        conn: Pool<ConnectionManager<SqliteConnection>>,
+        query: Query,
+        hits: Option<Vec<i32>>,
+    ) -> Self {
+        Self {
+            query: Some(query),
+            hits,
+            ..Self::all(conn)
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: u32) -> Self {
+        self.batch_size = batch_size.max(1).into();
+        self
+    }
+
+    fn predicate(&self) -> RecordPredicate<'_> {
+        match &self.query {
+            Some(query) => query_filter(query),
+            None => Box::new(sql::<Bool>("1")),
+        }
+    }
+
+    fn load_batch(&mut self) -> ChatRecordResult<()> {
+        use schema::records::dsl::*;
+        let mut conn = self.conn.get()?;
+        if let Some(hits) = self.hits.take() {
+            let ordered = records
+                .filter(self.predicate().and(ids_filter(&hits)))
+                .order((timestamp.asc(), id.asc()))
+                .select(id.assume_not_null())
+                .load::<i32>(&mut conn)?;
+            self.ordered = Some(ordered.into());
+        }
+        let batch = match &mut self.ordered {
+            Some(ordered) => {
+                let chunk = ordered
+                    .drain(..ordered.len().min(self.batch_size as usize))
+                    .collect::<Vec<_>>();
+                self.done = ordered.is_empty();
+                records
+                    .filter(ids_filter(&chunk))
+                    .order((timestamp.asc(), id.asc()))
+                    .load::<Record>(&mut conn)?
+            }
+            None => {
+                let (batch, has_more) =
+                    load_page(&mut conn, self.predicate(), &self.cursor, self.batch_size)?;
+                if let Some(last) = batch.last() {
+                    self.cursor = Cursor::after(last);
+                }
+                self.done = !has_more;
+                batch
+            }
+        };
+        let batch = match &self.query {
+            Some(query) if query.resolve_sender_name => resolve_sender_names(&mut conn, batch)?,
+            _ => batch,
+        };
+        self.batch.extend(batch);
+        Ok(())
+    }
+}
+
+impl Iterator for RecordIter {
+    type Item = ChatRecordResult<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.batch.is_empty() && !self.done {
+            if let Err(e) = self.load_batch() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+        self.batch.pop_front().map(Ok)
+    }
+}
+
+#[test]
+fn test_record_iter() {
+    let mut recorder = test_recorder("iter");
+    for i in 0..5 {
+        assert!(recorder
+            .insert_or_update_record(
+                &Record {
+                    group_id: if i % 2 == 0 { "even" } else { "odd" }.into(),
+                    ..test_record("test_iter", if i < 3 { "早上好" } else { "晚安" }, 5 - i)
+                },
+                None
+            )
+            .unwrap());
+    }
+    recorder.refresh_index().unwrap();
+    let timestamps = |query: Query| {
+        recorder
+            .iter_records(query)
+            .unwrap()
+            .with_batch_size(2)
+            .map(|record| record.map(|record| record.timestamp))
+            .collect::<ChatRecordResult<Vec<_>>>()
+            .unwrap()
+    };
+    assert_eq!(
+        timestamps(Query {
+            chat_type: Some("test_iter".into()),
+            limit: Some(1),
+            ..Default::default()
+        }),
+        vec![1, 2, 3, 4, 5]
+    );
+    assert_eq!(
+        timestamps(Query {
+            group_id: Some("even".into()),
+            ..Default::default()
+        }),
+        vec![1, 3, 5]
+    );
+    assert_eq!(
+        timestamps(Query {
+            group_id: Some("even".into()),
+            keyword: Some("早上".into()),
+            ..Default::default()
+        }),
+        vec![3, 5]
+    );
+    assert_eq!(
+        timestamps(Query {
+            chat_type: Some("test_iter".into()),
+            keyword: Some("早上".into()),
+            ..Default::default()
+        }),
+        vec![3, 4, 5]
+    );
+}
@@ -1,18 +1,54 @@
 mod attach;
 mod blob;
+mod conversation;
+mod embedding;
+mod expr;
+mod fingerprint;
+mod iter;
+mod person;
+mod query;
 mod record;
+mod sender;
+mod stats;
 
 use super::*;
-use attach::{insert_or_update_attach, remove_attachs};
-use blob::{get_blob, insert_blob, remove_blob};
+use attach::{
+    clear_attach_texts, get_attach_text, get_attachs, insert_attach_text, list_attachs,
+    merge_attachs, remove_attachs,
+};
+use blob::{get_blob, insert_blob};
+use conversation::{
+    get_conversation, get_participants, insert_or_update_conversation,
+    insert_or_update_participant, list_conversations, remove_conversation, remove_participant,
+};
 use diesel_migrations::{EmbeddedMigrations, MigrationHarness};
-use record::{get_record_id, insert_or_update_record, remove_record, remove_record_by_id};
-use std::collections::HashMap;
+use embedding::{get_embedding, get_embeddings, insert_or_update_embedding, remove_embedding};
+use expr::compile_expr;
+use fingerprint::{
+    fingerprint_record, get_fingerprint, get_fingerprint_candidates, get_fingerprinted,
+    get_fingerprints, remove_fingerprint,
+};
+pub use iter::RecordIter;
+use person::{
+    get_identities, get_person, get_person_by_identity, insert_or_update_person, link_identity,
+    remove_person, unlink_identity,
+};
+use query::{
+    attachment_filter, conversation_filter, has_records, ids_filter, load_page, metadata_filter,
+    person_filter, query_filter, query_scope, sort_by_ids, sort_query, string_filter,
+    RecordPredicate,
+};
+use record::{
+    get_record_by_type, get_record_id, insert_or_update_record, remove_record, remove_record_by_id,
+};
+use sender::{get_sender, get_sender_aliases, insert_or_update_sender_alias, resolve_sender_names};
+use stats::{count_records_by, count_records_by_weekday_hour, get_record_stats};
+use std::collections::{HashMap, HashSet};
 
 use anyhow::Context;
 use diesel::{
     connection::SimpleConnection,
-    dsl::{delete, exists, insert_into, select, update},
+    dsl::{delete, exists, insert_into, replace_into, select, update},
     prelude::*,
     r2d2::{ConnectionManager, Pool},
     result::Error as DieselError,
@@ -25,8 +61,15 @@ const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
 pub struct SqliteChatRecorder {
     conn: Pool<ConnectionManager<SqliteConnection>>,
     indexer: ContentIndexer,
+    vectors: VectorIndexer,
+    embedder: Option<Embedder>,
+    extractors: TextExtractors,
+    attach_policy: AttachmentPolicy,
 }
 
+const RRF_K: f32 = 60.0;
+const HYBRID_CANDIDATES: usize = 100;
+
 impl SqliteChatRecorder {
     pub fn new<P: AsRef<Path>>(db_name: P) -> ChatRecordResult<Self> {
         let manager = ConnectionManager::<SqliteConnection>::new(
@@ -45,6 +88,10 @@ impl SqliteChatRecorder {
         let mut recorder = Self {
             conn: pool,
             indexer: ContentIndexer::new()?,
+            vectors: VectorIndexer::new(),
+            embedder: None,
+            extractors: TextExtractors::default(),
+            attach_policy: AttachmentPolicy::default(),
         };
         recorder.refresh_index()?;
         Ok(recorder)
@@ -52,192 +99,1155 @@ impl SqliteChatRecorder {
 
     pub fn refresh_index(&mut self) -> ChatRecordResult<()> {
         self.indexer.cleanup_index()?;
-        self.indexer.gen_index(self.record_all()?)?;
+        let total = records::table
+            .count()
+            .get_result::<i64>(&mut self.conn.get()?)?;
+        self.indexer
+            .gen_index(RecordIter::all(self.conn.clone()), total as usize)?;
+        self.index_attachments()?;
+        self.vectors.clear();
+        let mut conn = self.conn.get()?;
+        for embedding in get_embeddings(&mut conn)? {
+            self.vectors
+                .insert(embedding.record_id, &embedding.get_vector());
+        }
+        Ok(())
+    }
+
+    /// index the values at the dot separated keys of the metadata, so keyword search matches them,
+    /// the index is rebuilt
+    pub fn set_indexed_metadata(
+        &mut self,
+        codec: MetadataCodec,
+        keys: &[String],
+    ) -> ChatRecordResult<()> {
+        self.indexer = ContentIndexer::with_metadata(codec, keys)?;
+        self.refresh_index()
+    }
+
+    /// extract the text of attachments matched by the extractors into the index,
+    /// texts are cached in database, so each attachment is extracted once, even if it fails
+    fn index_attachments(&mut self) -> ChatRecordResult<()> {
+        let mut conn = self.conn.get()?;
+        let attachs = list_attachs(&mut conn)?
+            .into_iter()
+            .filter(|attach| self.extractors.get(&attach.name).is_some())
+            .collect::<Vec<_>>();
+        let total = attachs.len();
+        let extractors = &self.extractors;
+        let mut extract = |attachment: &Attachment| -> ChatRecordResult<Option<String>> {
+            if let Some(text) = get_attach_text(&mut conn, attachment.hash, &attachment.name)? {
+                return Ok(text);
+            }
+            let blob = get_blob(&mut conn, attachment.hash)?;
+            let text = extractors.extract(&attachment.name, &blob);
+            insert_attach_text(
+                &mut conn,
+                attachment.hash,
+                &attachment.name,
+                text.as_deref(),
+            )?;
+            Ok(text)
+        };
+        let texts = attachs.into_iter().filter_map(|attachment| {
+            extract(&attachment)
+                .map(|text| text.map(|text| AttachmentText { attachment, text }))
+                .transpose()
+        });
+        self.indexer.gen_index(texts, total)
+    }
+
+    /// set the extractors of attachment text, the plain text and html extractors are used by default,
+    /// the cached texts are dropped and the attachments are extracted again on the next `refresh_index`
+    pub fn set_extractors(&mut self, extractors: TextExtractors) -> ChatRecordResult<()> {
+        let mut conn = self.conn.get()?;
+        clear_attach_texts(&mut conn)?;
+        self.extractors = extractors;
         Ok(())
     }
 
-    fn record_all(&self) -> ChatRecordResult<Vec<Record>> {
+    /// set how the attachments of updated records are merged with the stored attachments,
+    /// attachments are merged by name by default
+    pub fn set_attachment_policy(&mut self, policy: AttachmentPolicy) {
+        self.attach_policy = policy;
+    }
+
+    pub fn get_attachments(&self, record_id: i32) -> ChatRecordResult<Vec<Attachment>> {
+        let mut conn = self.conn.get()?;
+        get_attachs(&mut conn, record_id)
+    }
+
+    /// attachments whose extracted text is matched by the keyword of the query, in descending order
+    /// of relevance, limited to records matched by the filters of the query
+    pub fn search_attachments(&self, query: &Query) -> ChatRecordResult<Vec<AttachmentHit>> {
+        let keyword = match &query.keyword {
+            Some(keyword) => keyword,
+            None => return Ok(vec![]),
+        };
+        let hits = self.indexer.search_attachments(
+            query.get_offset(),
+            query.get_limit(),
+            keyword,
+            self.filter_ids(query)?.as_deref(),
+        )?;
+        let mut conn = self.conn.get()?;
+        let ids = hits.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        let result = records::table
+            .filter(ids_filter(&ids))
+            .load::<Record>(&mut conn)?;
+        let result = if query.resolve_sender_name {
+            resolve_sender_names(&mut conn, result)?
+        } else {
+            result
+        };
+        let result = result
+            .into_iter()
+            .map(|record| (record.get_id(), record))
+            .collect::<HashMap<_, _>>();
+        let attachs = attachments::table
+            .filter(attachments::id.eq_any(hits.iter().map(|(_, id)| *id)))
+            .load::<Attachment>(&mut conn)?
+            .into_iter()
+            .map(|attach| (attach.get_id(), attach))
+            .collect::<HashMap<_, _>>();
+        Ok(hits
+            .into_iter()
+            .filter_map(|(record_id, attachment_id)| {
+                Some(AttachmentHit {
+                    record: result.get(&record_id)?.clone(),
+                    attachment: attachs.get(&attachment_id)?.clone(),
+                })
+            })
+            .collect())
+    }
+
+    /// set the function used to embed the content of inserted or updated records,
+    /// records inserted before are embedded by `embed_records`
+    pub fn set_embedder<F>(&mut self, embedder: F)
+    where
+        F: Fn(&str) -> Option<Vec<f32>> + Send + Sync + 'static,
+    {
+        self.embedder = Some(Box::new(embedder));
+    }
+
+    /// embed all records without an embedding, returns the number of records embedded
+    pub fn embed_records(&mut self) -> ChatRecordResult<usize> {
+        if self.embedder.is_none() {
+            return Ok(0);
+        }
+        let mut conn = self.conn.get()?;
+        let mut count = 0;
+        for record in RecordIter::all(self.conn.clone()) {
+            let record = record?;
+            if !self.vectors.contains(record.get_id())
+                && self.embed_record(&mut conn, record.get_id(), &record.content)?
+            {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// the stored embedding of the record
+    pub fn get_embedding(&self, record_id: i32) -> ChatRecordResult<Option<Vec<f32>>> {
+        let mut conn = self.conn.get()?;
+        Ok(get_embedding(&mut conn, record_id)?.map(|embedding| embedding.get_vector()))
+    }
+
+    /// store the embedding of the content, the stale embedding is removed if the embedder returns
+    /// none, an empty or zero vector or a vector of another dimension than the stored embeddings
+    fn embed_record(
+        &mut self,
+        conn: &mut SqliteConnection,
+        record_id: i32,
+        content: &str,
+    ) -> ChatRecordResult<bool> {
+        let embedder = match &self.embedder {
+            Some(embedder) => embedder,
+            None => return Ok(false),
+        };
+        Ok(match embedder(content) {
+            Some(vector) if self.vectors.insert(record_id, &vector) => {
+                insert_or_update_embedding(conn, &Embedding::new(record_id, &vector))?
+            }
+            _ => {
+                self.vectors.remove(record_id);
+                remove_embedding(conn, record_id)?;
+                false
+            }
+        })
+    }
+
+    /// records nearest to the embedding of the record, scoped by the filters, keyword and entities
+    /// of the query, the record itself is excluded, `query.limit` is the number of results and
+    /// the offset, cursor and sort of the query are ignored
+    pub fn get_similar_records<'a, R: Into<RecordType<'a>>>(
+        &self,
+        record: R,
+        query: &Query,
+    ) -> ChatRecordResult<Vec<ScoredRecord>> {
+        let mut conn = self.conn.get()?;
+        let record = match get_record_by_type(&mut conn, record.into())? {
+            Some(record) => record,
+            None => return Ok(vec![]),
+        };
+        let vector = match self.vectors.get(record.get_id()) {
+            Some(vector) => vector.to_vec(),
+            None => match self.embed(&record.content) {
+                Some(vector) => vector,
+                None => return Ok(vec![]),
+            },
+        };
+        let scores = self.vectors.search(
+            &vector,
+            self.scope_ids(query)?.as_deref(),
+            record.id,
+            query.get_limit() as usize,
+        );
+        self.load_scored_records(scores, query)
+    }
+
+    /// records nearest to the embedding of the text, same as `get_similar_records`
+    pub fn search_similar(&self, text: &str, query: &Query) -> ChatRecordResult<Vec<ScoredRecord>> {
+        let vector = match self.embed(text) {
+            Some(vector) => vector,
+            None => return Ok(vec![]),
+        };
+        let scores = self.vectors.search(
+            &vector,
+            self.scope_ids(query)?.as_deref(),
+            None,
+            query.get_limit() as usize,
+        );
+        self.load_scored_records(scores, query)
+    }
+
+    /// keyword and vector rankings of the text fused by reciprocal rank, scoped like `get_similar_records`,
+    /// the keyword ranking is used alone if no embedder is set
+    pub fn hybrid_search(&self, text: &str, query: &Query) -> ChatRecordResult<Vec<ScoredRecord>> {
+        let limit = query.get_limit() as usize;
+        let candidates = limit.max(HYBRID_CANDIDATES);
+        let ids = self.scope_ids(query)?;
+        let keyword = self.indexer.search_with_sort(
+            0,
+            candidates as i64,
+            &SearchRequest::Lenient(text.into()),
+            ids.as_deref(),
+            QuerySort::Relevance,
+        )?;
+        let vector = match self.embed(text) {
+            Some(vector) => self
+                .vectors
+                .search(&vector, ids.as_deref(), None, candidates)
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect(),
+            None => vec![],
+        };
+        let mut fused = HashMap::<i32, f32>::new();
+        for ranking in [keyword, vector] {
+            for (rank, id) in ranking.into_iter().enumerate() {
+                *fused.entry(id).or_default() += 1.0 / (RRF_K + rank as f32 + 1.0);
+            }
+        }
+        let mut fused = fused.into_iter().collect::<Vec<_>>();
+        fused.sort_by(|(a_id, a), (b_id, b)| b.total_cmp(a).then(a_id.cmp(b_id)));
+        fused.truncate(limit);
+        self.load_scored_records(fused, query)
+    }
+
+    fn embed(&self, text: &str) -> Option<Vec<f32>> {
+        self.embedder.as_ref().and_then(|embedder| embedder(text))
+    }
+
+    /// ids of records matched by the filters, keyword and entities of the query,
+    /// none if the query has none of them
+    fn scope_ids(&self, query: &Query) -> ChatRecordResult<Option<Vec<i32>>> {
         use schema::records::dsl::*;
-        Ok(records.load::<Record>(&mut self.conn.get()?)?)
+        let search = query.get_search();
+        Ok(if query.has_filters() {
+            let mut filtered = records.filter(query_filter(query)).into_boxed();
+            if let Some(keyword) = &search {
+                filtered = filtered.filter(ids_filter(&self.indexer.search_all(keyword)?));
+            }
+            Some(
+                filtered
+                    .select(id.assume_not_null())
+                    .load::<i32>(&mut self.conn.get()?)?,
+            )
+        } else if let Some(keyword) = &search {
+            Some(self.indexer.search_all(keyword)?)
+        } else {
+            None
+        })
     }
 
-    fn record_query(&self, query: Query) -> ChatRecordResult<Vec<Record>> {
+    fn load_scored_records(
+        &self,
+        scores: Vec<(i32, f32)>,
+        query: &Query,
+    ) -> ChatRecordResult<Vec<ScoredRecord>> {
+        let mut conn = self.conn.get()?;
+        let ids = scores.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        let result = sort_by_ids(
+            records::table
+                .filter(ids_filter(&ids))
+                .load::<Record>(&mut conn)?,
+            &ids,
+        );
+        let result = if query.resolve_sender_name {
+            resolve_sender_names(&mut conn, result)?
+        } else {
+            result
+        };
+        let scores = scores.into_iter().collect::<HashMap<_, _>>();
+        Ok(result
+            .into_iter()
+            .map(|record| ScoredRecord {
+                score: scores.get(&record.get_id()).copied().unwrap_or_default(),
+                record,
+            })
+            .collect())
+    }
+
+    /// most frequent values of the entity in records matched by the query,
+    /// the offset and limit of the query are ignored
+    pub fn top_entities(
+        &self,
+        kind: EntityKind,
+        query: &Query,
+        limit: u32,
+    ) -> ChatRecordResult<Vec<EntityCount>> {
+        self.indexer.top_entities(
+            kind,
+            query.get_search().as_ref(),
+            self.filter_ids(query)?.as_deref(),
+            limit,
+        )
+    }
+
+    /// records matched by the query, with the facet counts of all records matched by
+    /// the query regardless of its offset and limit
+    pub fn get_record_with_facets(
+        &self,
+        query: Query,
+        facets: &[FacetField],
+    ) -> ChatRecordResult<FacetedRecords> {
+        let facets = self.indexer.facet_counts(
+            query.get_search().as_ref(),
+            self.filter_ids(&query)?.as_deref(),
+            facets,
+        )?;
+        Ok(FacetedRecords {
+            records: self.record_query(query)?,
+            facets,
+        })
+    }
+
+    /// count records matched by the query over time, aggregated by the indexer
+    pub fn get_record_histogram(
+        &self,
+        query: &Query,
+        interval: HistogramInterval,
+    ) -> ChatRecordResult<Vec<HistogramBucket>> {
+        self.indexer.date_histogram(
+            query.get_search().as_ref(),
+            self.filter_ids(query)?.as_deref(),
+            interval,
+        )
+    }
+
+    /// records with content similar to the record, e.g. the same message forwarded to other groups,
+    /// in descending order of relevance, scoped by the query, the record itself is excluded,
+    /// the cursor and sort of the query are ignored
+    pub fn get_more_like_this<'a, R: Into<RecordType<'a>>>(
+        &self,
+        record: R,
+        query: &Query,
+        options: &MoreLikeThisOptions,
+    ) -> ChatRecordResult<Vec<Record>> {
         use schema::records::dsl::*;
-        let default_query = records.filter(
-            timestamp
-                .le(query.before.unwrap_or_else(get_now))
-                .and(timestamp.ge(query.after.unwrap_or(0))),
+        let mut conn = self.conn.get()?;
+        let record = match get_record_by_type(&mut conn, record.into())? {
+            Some(record) => record,
+            None => return Ok(vec![]),
+        };
+        let ids = self.indexer.more_like_this(
+            &record.content,
+            options,
+            query.get_search().as_ref(),
+            self.filter_ids(query)?.as_deref(),
+            record.id,
+            query.get_offset(),
+            query.get_limit(),
+        )?;
+        let result = sort_by_ids(
+            records.filter(ids_filter(&ids)).load::<Record>(&mut conn)?,
+            &ids,
         );
-        Ok(if let Some(keyword) = &query.keyword {
-            default_query
-                .filter(id.eq_any(self.indexer.search(
-                    query.get_offset(),
-                    query.get_limit(),
-                    keyword,
-                )?))
-                .load::<Record>(&mut self.conn.get()?)?
+        Ok(if query.resolve_sender_name {
+            resolve_sender_names(&mut conn, result)?
         } else {
-            default_query
-                .filter(
-                    chat_type
-                        .like(query.get_chat_type())
-                        .and(owner_id.like(query.get_owner_id()))
-                        .and(group_id.like(query.get_group_id()))
-                        .and(sender_id.like(query.get_sender_id()))
-                        .and(sender_name.like(query.get_sender_name())),
-                )
-                .offset(query.get_offset())
-                .limit(query.get_limit())
-                .load::<Record>(&mut self.conn.get()?)?
+            result
         })
     }
 
-    fn record_auto_insert(
-        &mut self,
-        record: &Record,
-        attachs: HashMap<String, Vec<u8>>,
-        merger: MetadataMerger<Self>,
-    ) -> ChatRecordResult<bool> {
+    /// fingerprint all records without a fingerprint, returns the number of records fingerprinted
+    pub fn fingerprint_records(&mut self) -> ChatRecordResult<usize> {
+        let mut conn = self.conn.get()?;
+        let fingerprinted = get_fingerprinted(&mut conn)?
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let mut count = 0;
+        for record in RecordIter::all(self.conn.clone()) {
+            let record = record?;
+            if !fingerprinted.contains(&record.get_id())
+                && fingerprint_record(&mut conn, record.get_id(), &record.content)?
+            {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// near-duplicates of the record within `max_distance` bits of its fingerprint, the record included,
+    /// duplicates more than 3 bits away may be missed if they share no band with the record
+    pub fn get_duplicates<'a, R: Into<RecordType<'a>>>(
+        &self,
+        record: R,
+        max_distance: u32,
+    ) -> ChatRecordResult<DuplicateCluster> {
+        use schema::records::dsl::*;
+        let mut conn = self.conn.get()?;
+        let record = match get_record_by_type(&mut conn, record.into())? {
+            Some(record) => record,
+            None => return Ok(Default::default()),
+        };
+        let fingerprint = get_fingerprint(&mut conn, record.get_id())?
+            .or_else(|| Fingerprint::new(record.get_id(), &record.content));
+        let fingerprint = match fingerprint {
+            Some(fingerprint) => fingerprint,
+            None => {
+                return Ok(DuplicateCluster {
+                    records: vec![record],
+                })
+            }
+        };
+        let ids = get_fingerprint_candidates(&mut conn, &fingerprint)?
+            .into_iter()
+            .filter(|candidate| candidate.distance(&fingerprint) <= max_distance)
+            .map(|candidate| candidate.record_id)
+            .chain(record.id)
+            .collect::<Vec<_>>();
+        Ok(DuplicateCluster {
+            records: records
+                .filter(ids_filter(&ids))
+                .order((timestamp.asc(), id.asc()))
+                .load(&mut conn)?,
+        })
+    }
+
+    /// clusters of near-duplicates among records matched by the query, largest first,
+    /// records in a cluster are chained by fingerprints within `max_distance` bits,
+    /// at most `max_clusters` clusters are returned, the offset and limit of the query are ignored
+    pub fn cluster_duplicates(
+        &self,
+        query: &Query,
+        max_distance: u32,
+        max_clusters: Option<usize>,
+    ) -> ChatRecordResult<Vec<DuplicateCluster>> {
+        use schema::records::dsl::*;
         let mut conn = self.conn.get()?;
-        Ok(
-            insert_or_update_record(&mut conn, self, record, &attachs, merger)? && {
-                let mut len = 0;
-                let record_id = get_record_id(&mut conn, record)?;
-                if record_id > 0 {
-                    for (name, blob) in attachs.iter() {
-                        insert_or_update_attach(&mut conn, blob.clone(), name.clone(), record_id)?;
-                        len += 1;
+        let mut fingerprints = get_fingerprints(&mut conn)?;
+        if let Some(ids) = self.scope_ids(query)? {
+            let ids = ids.into_iter().collect::<HashSet<_>>();
+            fingerprints.retain(|fingerprint| ids.contains(&fingerprint.record_id));
+        }
+        // records with the same simhash are compared once
+        let mut hashes: HashMap<i64, Vec<i32>> = HashMap::new();
+        let mut unique = vec![];
+        for fingerprint in fingerprints {
+            let ids = hashes.entry(fingerprint.simhash).or_default();
+            if ids.is_empty() {
+                unique.push(fingerprint.clone());
+            }
+            ids.push(fingerprint.record_id);
+        }
+        let mut buckets: HashMap<(usize, i32), Vec<usize>> = HashMap::new();
+        for (i, fingerprint) in unique.iter().enumerate() {
+            for band in fingerprint.bands().iter().enumerate() {
+                buckets.entry((band.0, *band.1)).or_default().push(i);
+            }
+        }
+        let mut parents = (0..unique.len()).collect::<Vec<_>>();
+        fn find(parents: &mut [usize], mut i: usize) -> usize {
+            while parents[i] != i {
+                parents[i] = parents[parents[i]];
+                i = parents[i];
+            }
+            i
+        }
+        for ((band, _), bucket) in buckets.iter() {
+            for (n, a) in bucket.iter().enumerate() {
+                let bands = unique[*a].bands();
+                for b in &bucket[n + 1..] {
+                    // pairs sharing a lower band are compared in its bucket
+                    let other = unique[*b].bands();
+                    if (0..*band).any(|lower| bands[lower] == other[lower]) {
+                        continue;
+                    }
+                    let (root_a, root_b) = (find(&mut parents, *a), find(&mut parents, *b));
+                    if root_a != root_b && unique[*a].distance(&unique[*b]) <= max_distance {
+                        parents[root_a] = root_b;
                     }
-                    len
-                } else {
-                    0
                 }
-            } == attachs
-                .len(),
+            }
+        }
+        let mut clusters: HashMap<usize, Vec<i32>> = HashMap::new();
+        for (i, fingerprint) in unique.iter().enumerate() {
+            clusters
+                .entry(find(&mut parents, i))
+                .or_default()
+                .extend(&hashes[&fingerprint.simhash]);
+        }
+        let mut clusters = clusters
+            .into_values()
+            .filter(|ids| ids.len() > 1)
+            .collect::<Vec<_>>();
+        // clusters are ordered and limited by the times of their records before loading them
+        let timestamps = records
+            .filter(ids_filter(&clusters.concat()))
+            .select((id.assume_not_null(), timestamp))
+            .load::<(i32, i64)>(&mut conn)?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let earliest = |ids: &[i32]| {
+            ids.iter()
+                .filter_map(|record_id| Some((*timestamps.get(record_id)?, *record_id)))
+                .min()
+        };
+        clusters.sort_by_cached_key(|ids| (std::cmp::Reverse(ids.len()), earliest(ids)));
+        if let Some(max_clusters) = max_clusters {
+            clusters.truncate(max_clusters);
+        }
+        clusters
+            .into_iter()
+            .map(|ids| {
+                Ok(DuplicateCluster {
+                    records: records
+                        .filter(ids_filter(&ids))
+                        .order((timestamp.asc(), id.asc()))
+                        .load(&mut conn)?,
+                })
+            })
+            .collect()
+    }
+
+    /// ids of records matched by the filters of the query in database, none if it has no filter
+    fn filter_ids(&self, query: &Query) -> ChatRecordResult<Option<Vec<i32>>> {
+        use schema::records::dsl::*;
+        Ok(if query.has_filters() {
+            Some(
+                records
+                    .filter(query_filter(query))
+                    .select(id.assume_not_null())
+                    .load::<i32>(&mut self.conn.get()?)?,
+            )
+        } else {
+            None
+        })
+    }
+
+    /// ids of all records matched by the keyword of the query, none if it has no keyword
+    fn search_hits(&self, query: &Query) -> ChatRecordResult<Option<Vec<i32>>> {
+        Ok(match query.get_search() {
+            Some(keyword) => Some(self.indexer.search_all(&keyword)?),
+            None => None,
+        })
+    }
+
+    /// stream the records matched by the query without loading them all at once
+    pub fn iter_records(&self, query: Query) -> ChatRecordResult<RecordIter> {
+        let ids = self.search_hits(&query)?;
+        Ok(RecordIter::new(self.conn.clone(), query, ids))
+    }
+
+    fn record_query(&self, query: Query) -> ChatRecordResult<Vec<Record>> {
+        use schema::records::dsl::*;
+        let result = if let Some(keyword) = query.get_search() {
+            if query.has_filters() {
+                self.record_search_filtered(&query, &keyword)?
+            } else {
+                self.record_search(&query, &keyword)?
+            }
+        } else if let Some(cursor) = &query.cursor {
+            let mut conn = self.conn.get()?;
+            load_page(&mut conn, query_filter(&query), cursor, query.get_limit())?.0
+        } else {
+            sort_query(
+                records.filter(query_filter(&query)).into_boxed(),
+                query.get_sort(),
+            )
+            .offset(query.get_offset())
+            .limit(query.get_limit())
+            .load::<Record>(&mut self.conn.get()?)?
+        };
+        Ok(if query.resolve_sender_name {
+            let mut conn = self.conn.get()?;
+            resolve_sender_names(&mut conn, result)?
+        } else {
+            result
+        })
+    }
+
+    /// page in the indexer, used if there is no filter to apply in database
+    fn record_search(
+        &self,
+        query: &Query,
+        keyword: &SearchRequest,
+    ) -> ChatRecordResult<Vec<Record>> {
+        use schema::records::dsl::*;
+        let ids = self.indexer.search_with_sort(
+            query.get_offset(),
+            query.get_limit(),
+            keyword,
+            None,
+            query.get_sort(),
+        )?;
+        Ok(sort_by_ids(
+            records
+                .filter(query_scope(query))
+                .filter(id.eq_any(&ids))
+                .load::<Record>(&mut self.conn.get()?)?,
+            &ids,
+        ))
+    }
+
+    /// the hits are restricted to the records matched by the filters before paging,
+    /// in the indexer for relevance and in database for other sorts, so pages are not cut short
+    fn record_search_filtered(
+        &self,
+        query: &Query,
+        keyword: &SearchRequest,
+    ) -> ChatRecordResult<Vec<Record>> {
+        use schema::records::dsl::*;
+        Ok(match query.get_sort() {
+            QuerySort::Relevance => {
+                let ids = self.indexer.search_with_sort(
+                    query.get_offset(),
+                    query.get_limit(),
+                    keyword,
+                    self.filter_ids(query)?.as_deref(),
+                    QuerySort::Relevance,
+                )?;
+                sort_by_ids(
+                    records
+                        .filter(ids_filter(&ids))
+                        .load::<Record>(&mut self.conn.get()?)?,
+                    &ids,
+                )
+            }
+            sort => sort_query(
+                records
+                    .filter(query_filter(query))
+                    .filter(ids_filter(&self.indexer.search_all(keyword)?))
+                    .into_boxed(),
+                sort,
+            )
+            .offset(query.get_offset())
+            .limit(query.get_limit())
+            .load::<Record>(&mut self.conn.get()?)?,
+        })
+    }
+
+    /// number of records matched by the query, the offset and limit of the query are ignored
+    pub fn count_records(&self, query: &Query) -> ChatRecordResult<i64> {
+        use schema::records::dsl::*;
+        let mut filtered = records.filter(query_filter(query)).into_boxed();
+        if let Some(keyword) = query.get_search() {
+            if !query.has_filters() {
+                return Ok(self.indexer.count(&keyword)? as i64);
+            }
+            filtered = filtered.filter(ids_filter(&self.indexer.search_all(&keyword)?));
+        }
+        Ok(filtered.count().get_result(&mut self.conn.get()?)?)
+    }
+
+    /// whether any record is matched by the query, searches stop at the first hit in the indexer
+    pub fn exists(&self, query: &Query) -> ChatRecordResult<bool> {
+        use schema::records::dsl::*;
+        Ok(match query.get_search() {
+            Some(search) => !self
+                .indexer
+                .search_with_sort(
+                    0,
+                    1,
+                    &search,
+                    self.filter_ids(query)?.as_deref(),
+                    QuerySort::Relevance,
+                )?
+                .is_empty(),
+            None => select(exists(records.filter(query_filter(query))))
+                .get_result(&mut self.conn.get()?)?,
+        })
+    }
+
+    /// query records by a boolean expression, `QuerySort::Relevance` is the same as `TimestampAsc`
+    pub fn get_record_by_expr(
+        &self,
+        expr: &QueryExpr,
+        sort: QuerySort,
+        offset: u64,
+        limit: u32,
+    ) -> ChatRecordResult<Vec<Record>> {
+        use schema::records::dsl::*;
+        Ok(sort_query(
+            records
+                .filter(compile_expr(expr, &self.indexer)?)
+                .into_boxed(),
+            sort,
         )
+        .offset(offset.min(i64::MAX as u64) as i64)
+        .limit(limit.into())
+        .load::<Record>(&mut self.conn.get()?)?)
     }
 
-    pub fn get_blob(&self, hash: i64) -> ChatRecordResult<Vec<u8>> {
+    /// keyset pagination on `(timestamp, id)`, pages forward from the oldest record if
+    /// `query.cursor` is none, the keyword and offset of the query are ignored
+    pub fn get_record_page(&self, query: Query) -> ChatRecordResult<RecordPage> {
+        let cursor = query.cursor.unwrap_or_else(Cursor::oldest);
         let mut conn = self.conn.get()?;
-        get_blob(&mut conn, hash)
+        let (result, has_more) =
+            load_page(&mut conn, query_filter(&query), &cursor, query.get_limit())?;
+        let result = if query.resolve_sender_name {
+            resolve_sender_names(&mut conn, result)?
+        } else {
+            result
+        };
+        let (prev, next) = match (result.first(), result.last()) {
+            (Some(first), Some(last)) => {
+                let (prev, next) = (Cursor::before(first), Cursor::after(last));
+                // the side of the page at the cursor is checked for more records
+                let (has_prev, has_next) = match cursor.direction {
+                    CursorDirection::After => (
+                        has_records(&mut conn, query_filter(&query), &prev)?,
+                        has_more,
+                    ),
+                    CursorDirection::Before => (
+                        has_more,
+                        has_records(&mut conn, query_filter(&query), &next)?,
+                    ),
+                };
+                (
+                    Some(prev).filter(|_| has_prev),
+                    Some(next).filter(|_| has_next),
+                )
+            }
+            _ => (None, None),
+        };
+        Ok(RecordPage {
+            records: result,
+            prev,
+            next,
+        })
     }
-}
 
-fn default_metadata_merger(
-    _recorder: &SqliteChatRecorder,
-    _attachs: &Attachments,
-    _old: Vec<u8>,
-    new: Vec<u8>,
-) -> Option<Vec<u8>> {
-    Some(new)
-}
+    /// the record with `before` preceding and `after` following records in the same conversation,
+    /// returns none if the record does not exist
+    pub fn get_record_context<'a, R: Into<RecordType<'a>>>(
+        &self,
+        record: R,
+        before: u32,
+        after: u32,
+    ) -> ChatRecordResult<Option<RecordContext>> {
+        let mut conn = self.conn.get()?;
+        Ok(match get_record_by_type(&mut conn, record.into())? {
+            Some(record) => {
+                let (preceding, _) = load_page(
+                    &mut conn,
+                    conversation_filter(&record),
+                    &Cursor::before(&record),
+                    before.into(),
+                )?;
+                let (following, _) = load_page(
+                    &mut conn,
+                    conversation_filter(&record),
+                    &Cursor::after(&record),
+                    after.into(),
+                )?;
+                Some(RecordContext {
+                    record,
+                    before: preceding,
+                    after: following,
+                })
+            }
+            None => None,
+        })
+    }
 
-impl<'a> ChatRecorder<'a> for SqliteChatRecorder {
-    fn insert_or_update_record<R>(
+    /// same as `get_record`, with `before` preceding and `after` following records of each result
+    pub fn get_record_with_context(
+        &self,
+        query: Query,
+        before: u32,
+        after: u32,
+    ) -> ChatRecordResult<Vec<RecordContext>> {
+        Ok(self
+            .record_query(query)?
+            .iter()
+            .map(|record| self.get_record_context(record, before, after))
+            .collect::<ChatRecordResult<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+
+    /// insert or update the record like `insert_or_update_record`, returns the changes of its
+    /// attachments by the attachment policy, none if the record is not stored,
+    /// attachments which could not be written are reported as failed
+    pub fn insert_or_update_record_with_report<'a, R: Into<RecordType<'a>>>(
         &mut self,
         record: R,
-        merger: Option<MetadataMerger<Self>>,
-    ) -> ChatRecordResult<bool>
-    where
-        R: Into<RecordType<'a>>,
-    {
-        let merger = merger.unwrap_or(default_metadata_merger);
-        Ok(match record.into() {
-            RecordType::Id(_) => false,
+        merger: Option<&mut dyn MetadataMerger<Self>>,
+    ) -> ChatRecordResult<Option<AttachmentReport>> {
+        match record.into() {
+            RecordType::Id(_) => Ok(None),
             RecordType::Record(record) => {
-                self.record_auto_insert(&record, Default::default(), merger)?
+                self.record_auto_insert(&record, Default::default(), merger)
             }
             RecordType::RecordRef(record) => {
-                self.record_auto_insert(record, Default::default(), merger)?
+                self.record_auto_insert(record, Default::default(), merger)
             }
             RecordType::RecordWithAttaches {
                 record,
                 attaches: attachs,
-            } => self.record_auto_insert(&record, attachs, merger)?,
+            } => self.record_auto_insert(&record, attachs, merger),
             RecordType::RecordRefWithAttaches {
                 record,
                 attaches: attachs,
-            } => self.record_auto_insert(record, attachs, merger)?,
-        })
+            } => self.record_auto_insert(record, attachs, merger),
+        }
     }
 
-    fn remove_record<R: Into<RecordType<'a>>>(&mut self, record: R) -> ChatRecordResult<bool> {
+    fn record_auto_insert(
+        &mut self,
+        record: &Record,
+        attachs: HashMap<String, Vec<u8>>,
+        merger: Option<&mut dyn MetadataMerger<Self>>,
+    ) -> ChatRecordResult<Option<AttachmentReport>> {
         let mut conn = self.conn.get()?;
-        Ok(match record.into() {
-            RecordType::Id(id) => remove_record_by_id(&mut conn, id)? == 1,
-            RecordType::Record(record) | RecordType::RecordWithAttaches { record, .. } => {
-                remove_record(&mut conn, &record)? == 1
-                    && remove_attachs(&mut conn, record.get_id())?
+        // a failed or conflicting update leaves neither the sender alias nor the attachments behind
+        let stored = conn.transaction(|conn| {
+            if !record.sender_name.is_empty() {
+                insert_or_update_sender_alias(
+                    conn,
+                    &SenderAlias {
+                        id: None,
+                        chat_type: record.chat_type.clone(),
+                        sender_id: record.sender_id.clone(),
+                        name: record.sender_name.clone(),
+                        timestamp: record.timestamp,
+                    },
+                )?;
             }
-            RecordType::RecordRef(record) | RecordType::RecordRefWithAttaches { record, .. } => {
-                remove_record(&mut conn, record)? == 1
-                    && remove_attachs(&mut conn, record.get_id())?
+            let updated = insert_or_update_record(conn, self, record, &attachs, merger)?;
+            let record_id = if updated {
+                get_record_id(conn, record)?
+            } else {
+                0
+            };
+            if record_id > 0 {
+                fingerprint_record(conn, record_id, &record.content)?;
+                let report = merge_attachs(conn, record_id, &attachs, self.attach_policy)?;
+                Ok(Some((record_id, report)))
+            } else {
+                Ok::<_, ChatRecordError>(None)
+            }
+        })?;
+        // the embedder may be slow, so it runs after the write lock is released
+        Ok(match stored {
+            Some((record_id, report)) => {
+                self.embed_record(&mut conn, record_id, &record.content)?;
+                Some(report)
             }
+            None => None,
         })
     }
 
-    fn get_record(&self, query: Query) -> ChatRecordResult<Vec<Record>> {
-        self.record_query(query)
+    pub fn get_blob(&self, hash: i64) -> ChatRecordResult<Vec<u8>> {
+        let mut conn = self.conn.get()?;
+        get_blob(&mut conn, hash)
     }
-}
 
-#[test]
-fn test_chat_record() {
-    let mut recorder = SqliteChatRecorder::new("record.db").unwrap();
-    let record = Record {
-        chat_type: "testaasdavxz".into(),
-        owner_id: "asdasdasdaaaa".into(),
-        group_id: "asdasdasd".into(),
-        sender_id: "people_daily".into(),
-        sender_name: "人民日报".into(),
-        content:
-            "张华考上了北京大学；李萍进了中等技术学校；我在百货公司当售货员：我们都有光明的前途"
-                .into(),
-        timestamp: chrono::Local::now()
-            .naive_utc()
-            .and_utc()
-            .timestamp_millis(),
-        ..Default::default()
-    };
-    assert_eq!(
-        recorder.insert_or_update_record(&record, None).unwrap(),
-        true
-    );
-    let record1 = Record {
-        chat_type: "testaasdavxz".into(),
-        owner_id: "asdasdasdaaaa".into(),
-        group_id: "asdasdasd".into(),
-        sender_id: "news".into(),
-        sender_name: "新闻".into(),
-        content: "Intel线路图显示他们想恢复两年升级一次工艺，2029年有1.4nm".into(),
-        timestamp: chrono::Local::now()
-            .naive_utc()
-            .and_utc()
-            .timestamp_millis(),
-        ..Default::default()
-    };
-    assert_eq!(
-        recorder
-            .insert_or_update_record(
-                (
-                    &record1,
-                    [("test".into(), vec![0, 1, 2, 3])]
-                        .iter()
-                        .cloned()
-                        .collect()
-                ),
-                None
-            )
-            .unwrap(),
-        true
-    );
-    recorder.refresh_index().unwrap();
+    pub fn insert_or_update_conversation(
+        &mut self,
+        conversation: &Conversation,
+        avatar: Option<Vec<u8>>,
+    ) -> ChatRecordResult<bool> {
+        let mut conn = self.conn.get()?;
+        if let Some(avatar) = avatar {
+            let blob = Blob::new(avatar);
+            if !insert_blob(&mut conn, &blob)? {
+                return Ok(false);
+            }
+            let mut conversation = conversation.clone();
+            conversation.avatar = Some(blob.hash);
+            insert_or_update_conversation(&mut conn, &conversation)
+        } else {
+            insert_or_update_conversation(&mut conn, conversation)
+        }
+    }
+
+    pub fn get_conversation(
+        &self,
+        chat_type: &str,
+        owner_id: &str,
+        group_id: &str,
+    ) -> ChatRecordResult<Option<Conversation>> {
+        let mut conn = self.conn.get()?;
+        get_conversation(&mut conn, chat_type, owner_id, group_id)
+    }
+
+    pub fn remove_conversation(&mut self, conversation: &Conversation) -> ChatRecordResult<bool> {
+        let mut conn = self.conn.get()?;
+        Ok(remove_conversation(&mut conn, conversation)? == 1)
+    }
+
+    pub fn insert_or_update_participant(
+        &mut self,
+        participant: &Participant,
+    ) -> ChatRecordResult<bool> {
+        let mut conn = self.conn.get()?;
+        insert_or_update_participant(&mut conn, participant)
+    }
+
+    pub fn get_participants(&self, conversation_id: i32) -> ChatRecordResult<Vec<Participant>> {
+        let mut conn = self.conn.get()?;
+        get_participants(&mut conn, conversation_id)
+    }
+
+    pub fn remove_participant(&mut self, participant: &Participant) -> ChatRecordResult<bool> {
+        let mut conn = self.conn.get()?;
+        Ok(remove_participant(&mut conn, participant)? == 1)
+    }
+
+    pub fn insert_or_update_sender_alias(&mut self, alias: &SenderAlias) -> ChatRecordResult<bool> {
+        let mut conn = self.conn.get()?;
+        insert_or_update_sender_alias(&mut conn, alias)
+    }
+
+    pub fn get_sender(&self, chat_type: &str, sender_id: &str) -> ChatRecordResult<Option<Sender>> {
+        let mut conn = self.conn.get()?;
+        get_sender(&mut conn, chat_type, sender_id)
+    }
+
+    /// all names the sender has used, in chronological order, one alias per contiguous run of a name
+    pub fn get_sender_aliases(
+        &self,
+        chat_type: &str,
+        sender_id: &str,
+    ) -> ChatRecordResult<Vec<SenderAlias>> {
+        let mut conn = self.conn.get()?;
+        get_sender_aliases(&mut conn, chat_type, sender_id)
+    }
+
+    /// returns the id of the inserted or updated person
+    pub fn insert_or_update_person(&mut self, person: &Person) -> ChatRecordResult<i32> {
+        let mut conn = self.conn.get()?;
+        insert_or_update_person(&mut conn, person)
+    }
+
+    pub fn get_person(&self, person_id: i32) -> ChatRecordResult<Option<Person>> {
+        let mut conn = self.conn.get()?;
+        get_person(&mut conn, person_id)
+    }
+
+    pub fn remove_person(&mut self, person_id: i32) -> ChatRecordResult<bool> {
+        let mut conn = self.conn.get()?;
+        Ok(remove_person(&mut conn, person_id)? == 1)
+    }
+
+    pub fn link_identity(&mut self, identity: &PersonIdentity) -> ChatRecordResult<bool> {
+        let mut conn = self.conn.get()?;
+        link_identity(&mut conn, identity)
+    }
+
+    pub fn unlink_identity(&mut self, identity: &PersonIdentity) -> ChatRecordResult<bool> {
+        let mut conn = self.conn.get()?;
+        Ok(unlink_identity(&mut conn, identity)? == 1)
+    }
+
+    pub fn get_identities(&self, person_id: i32) -> ChatRecordResult<Vec<PersonIdentity>> {
+        let mut conn = self.conn.get()?;
+        get_identities(&mut conn, person_id)
+    }
+
+    pub fn get_person_by_identity(
+        &self,
+        chat_type: &str,
+        sender_id: &str,
+    ) -> ChatRecordResult<Option<Person>> {
+        let mut conn = self.conn.get()?;
+        get_person_by_identity(&mut conn, chat_type, sender_id)
+    }
+
+    /// count records matched by the query in each group,
+    /// `query.limit` limits the number of groups returned, all groups are returned if it is none
+    pub fn count_records_by(
+        &self,
+        query: &Query,
+        group: StatsGroup,
+    ) -> ChatRecordResult<Vec<StatsCount>> {
+        let hits = self.search_hits(query)?;
+        let mut conn = self.conn.get()?;
+        count_records_by(&mut conn, query, hits.as_deref(), group)
+    }
+
+    /// counts of records matched by the query by day of week and hour of day in the local time of
+    /// the utc offset in seconds, `counts[0][8]` is the count on sundays from 8 to 9 o'clock
+    pub fn count_records_by_weekday_hour(
+        &self,
+        query: &Query,
+        offset: i32,
+    ) -> ChatRecordResult<[[i64; 24]; 7]> {
+        let hits = self.search_hits(query)?;
+        let mut conn = self.conn.get()?;
+        count_records_by_weekday_hour(&mut conn, query, hits.as_deref(), offset)
+    }
+
+    /// aggregate statistics of records matched by the query
+    pub fn get_record_stats(&self, query: &Query) -> ChatRecordResult<RecordStats> {
+        let hits = self.search_hits(query)?;
+        let mut conn = self.conn.get()?;
+        get_record_stats(&mut conn, query, hits.as_deref())
+    }
+
+    /// list all conversations of the owner, sorted by last message time in descending order
+    pub fn list_conversations(&self, owner_id: &str) -> ChatRecordResult<Vec<ConversationSummary>> {
+        let mut conn = self.conn.get()?;
+        list_conversations(&mut conn, owner_id)
+    }
+}
+
+impl<'a> ChatRecorder<'a> for SqliteChatRecorder {
+    fn insert_or_update_record<R>(
+        &mut self,
+        record: R,
+        merger: Option<&mut dyn MetadataMerger<Self>>,
+    ) -> ChatRecordResult<bool>
+    where
+        R: Into<RecordType<'a>>,
+    {
+        Ok(self
+            .insert_or_update_record_with_report(record, merger)?
+            .is_some_and(|report| report.failed.is_empty()))
+    }
+
+    fn remove_record<R: Into<RecordType<'a>>>(&mut self, record: R) -> ChatRecordResult<bool> {
+        let mut conn = self.conn.get()?;
+        let record = record.into();
+        let record_id =
+            get_record_by_type(&mut conn, record.clone())?.map(|record| record.get_id());
+        let removed = match record {
+            RecordType::Id(id) => remove_record_by_id(&mut conn, id)? == 1,
+            RecordType::Record(record) | RecordType::RecordWithAttaches { record, .. } => {
+                remove_record(&mut conn, &record)? == 1
+                    && remove_attachs(&mut conn, record.get_id())?
+            }
+            RecordType::RecordRef(record) | RecordType::RecordRefWithAttaches { record, .. } => {
+                remove_record(&mut conn, record)? == 1
+                    && remove_attachs(&mut conn, record.get_id())?
+            }
+        };
+        if let Some(record_id) = record_id.filter(|_| removed) {
+            remove_fingerprint(&mut conn, record_id)?;
+            remove_embedding(&mut conn, record_id)?;
+            self.vectors.remove(record_id);
+        }
+        Ok(removed)
+    }
+
+    fn get_record(&self, query: Query) -> ChatRecordResult<Vec<Record>> {
+        self.record_query(query)
+    }
+}
+
+/// a recorder on a fresh database in the temporary directory, so tests can run in parallel and rerun
+#[cfg(test)]
+fn test_recorder(name: &str) -> SqliteChatRecorder {
+    let path = std::env::temp_dir().join(format!("gchdb_{}_{}.db", name, std::process::id()));
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+    SqliteChatRecorder::new(path).unwrap()
+}
+
+/// a record sent by `sender` in `group` of `owner`
+#[cfg(test)]
+fn test_record(chat_type: &str, content: &str, timestamp: i64) -> Record {
+    Record {
+        chat_type: chat_type.into(),
+        owner_id: "owner".into(),
+        group_id: "group".into(),
+        sender_id: "sender".into(),
+        sender_name: "sender".into(),
+        content: content.into(),
+        timestamp,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_chat_record() {
+    let mut recorder = SqliteChatRecorder::new("record.db").unwrap();
+    let record = Record {
+        chat_type: "testaasdavxz".into(),
+        owner_id: "asdasdasdaaaa".into(),
+        group_id: "asdasdasd".into(),
+        sender_id: "people_daily".into(),
+        sender_name: "人民日报".into(),
+        content:
+            "张华考上了北京大学；李萍进了中等技术学校；我在百货公司当售货员：我们都有光明的前途"
+                .into(),
+        timestamp: chrono::Local::now()
+            .naive_utc()
+            .and_utc()
+            .timestamp_millis(),
+        ..Default::default()
+    };
+    assert!(recorder.insert_or_update_record(&record, None).unwrap());
+    let record1 = Record {
+        chat_type: "testaasdavxz".into(),
+        owner_id: "asdasdasdaaaa".into(),
+        group_id: "asdasdasd".into(),
+        sender_id: "news".into(),
+        sender_name: "新闻".into(),
+        content: "Intel线路图显示他们想恢复两年升级一次工艺，2029年有1.4nm".into(),
+        timestamp: chrono::Local::now()
+            .naive_utc()
+            .and_utc()
+            .timestamp_millis(),
+        ..Default::default()
+    };
+    assert!(recorder
+        .insert_or_update_record(
+            (
+                &record1,
+                [("test".into(), vec![0, 1, 2, 3])]
+                    .iter()
+                    .cloned()
+                    .collect()
+            ),
+            None
+        )
+        .unwrap());
+    recorder.refresh_index().unwrap();
     println!(
         "{:?}",
         recorder
             .get_record(Query {
                 chat_type: Some("testaasdavxz".into()),
-                sender_name: Some("%日报".into()),
+                sender_name: Some(StringFilter::Like("%日报".into())),
                 ..Default::default()
             })
             .unwrap()
@@ -251,5 +1261,1017 @@ fn test_chat_record() {
             })
             .unwrap()
     );
-    assert_eq!(recorder.remove_record(&record).unwrap(), true);
+    assert!(recorder.remove_record(&record).unwrap());
+}
+
+#[test]
+fn test_search_request() {
+    let mut recorder = test_recorder("search");
+    for (i, content) in [
+        "hello world",
+        "help wanted",
+        "今天天气很好",
+        "c++ (rust) 问题",
+    ]
+    .iter()
+    .enumerate()
+    {
+        assert!(recorder
+            .insert_or_update_record(test_record("test_search", content, i as i64), None)
+            .unwrap());
+    }
+    recorder.refresh_index().unwrap();
+    let count = |keyword: SearchRequest| {
+        recorder.count_records(&Query {
+            keyword: Some(keyword),
+            ..Default::default()
+        })
+    };
+    assert!(matches!(
+        count("c++ (rust".into()),
+        Err(ChatRecordError::TantivyQueryError(_))
+    ));
+    assert_eq!(
+        count(SearchRequest::Lenient("c++ (rust".into())).unwrap(),
+        1
+    );
+    assert_eq!(count(SearchRequest::all("hello world")).unwrap(), 1);
+    assert_eq!(count(SearchRequest::all("hello wanted")).unwrap(), 0);
+    assert_eq!(count(SearchRequest::any("hello wanted")).unwrap(), 2);
+    assert_eq!(count(SearchRequest::Phrase("天气很".into())).unwrap(), 1);
+    assert_eq!(count(SearchRequest::Phrase("天很".into())).unwrap(), 0);
+    assert_eq!(count(SearchRequest::Prefix("hel".into())).unwrap(), 2);
+    assert_eq!(count(SearchRequest::Prefix("hello wor".into())).unwrap(), 1);
+    let fuzzy = |text: &str| SearchRequest::Fuzzy {
+        text: text.into(),
+        distance: 1,
+    };
+    assert_eq!(count(fuzzy("helo")).unwrap(), 2);
+    assert_eq!(count(fuzzy("wrold")).unwrap(), 1);
+    assert_eq!(count(SearchRequest::Regex("wa.*".into())).unwrap(), 1);
+}
+
+#[cfg(all(feature = "pinyin", feature = "zh-convert"))]
+#[test]
+fn test_chinese_variants() {
+    let mut recorder = test_recorder("variants");
+    for (i, content) in ["北", "背", "臺"].iter().enumerate() {
+        assert!(recorder
+            .insert_or_update_record(test_record("test_variants", content, i as i64), None)
+            .unwrap());
+    }
+    recorder.refresh_index().unwrap();
+    let count = |keyword: SearchRequest| {
+        recorder
+            .count_records(&Query {
+                keyword: Some(keyword),
+                ..Default::default()
+            })
+            .unwrap()
+    };
+    assert_eq!(count("bei".into()), 2);
+    assert_eq!(count("b".into()), 2);
+    assert_eq!(count("北".into()), 1);
+    assert_eq!(count("台".into()), 1);
+    assert_eq!(count(SearchRequest::all("tai")), 1);
+    assert_eq!(count(SearchRequest::Phrase("臺".into())), 1);
+}
+
+#[test]
+fn test_entities() {
+    let mut recorder = test_recorder("entities");
+    for (i, content) in [
+        "@alice 看看这个 https://www.example.com/a?b=1。",
+        "#Rust# 新版本发布 https://blog.rust-lang.org/2024 👍",
+        "@bob @alice 明天见👍👍 mail me: bob@example.com",
+        "#rust 好用 http://example.com:8080/b",
+    ]
+    .iter()
+    .enumerate()
+    {
+        assert!(recorder
+            .insert_or_update_record(test_record("test_entities", content, i as i64), None)
+            .unwrap());
+    }
+    recorder.refresh_index().unwrap();
+    let entities = Entities::extract("@bob @alice 明天见👍👍 mail me: bob@example.com");
+    assert_eq!(entities.mentions, vec!["bob", "alice"]);
+    assert_eq!(entities.emoji, vec!["👍"]);
+    assert!(entities.urls.is_empty());
+    let timestamps = |entities: Vec<(EntityKind, &str)>| {
+        recorder
+            .get_record(Query {
+                entities: entities
+                    .into_iter()
+                    .map(|(kind, value)| (kind, value.to_string()))
+                    .collect(),
+                ..Default::default()
+            })
+            .unwrap()
+            .iter()
+            .map(|record| record.timestamp)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        timestamps(vec![(EntityKind::Mention, "@alice")]),
+        vec![2, 0]
+    );
+    assert_eq!(
+        timestamps(vec![
+            (EntityKind::Mention, "alice"),
+            (EntityKind::Mention, "bob")
+        ]),
+        vec![2]
+    );
+    assert_eq!(
+        timestamps(vec![(EntityKind::Domain, "Example.com")]),
+        vec![3, 0]
+    );
+    assert_eq!(timestamps(vec![(EntityKind::Hashtag, "#RUST")]), vec![3, 1]);
+    // entity searches are sorted like keyword searches
+    assert_eq!(
+        Query {
+            entities: vec![(EntityKind::Hashtag, "rust".into())],
+            sort: Some(QuerySort::Relevance),
+            ..Default::default()
+        }
+        .get_sort(),
+        QuerySort::Relevance
+    );
+    // filtered relevance searches are paged in the indexer
+    let page = |offset| {
+        recorder
+            .get_record(Query {
+                chat_type: Some("test_entities".into()),
+                entities: vec![(EntityKind::Hashtag, "rust".into())],
+                sort: Some(QuerySort::Relevance),
+                offset: Some(offset),
+                limit: Some(1),
+                ..Default::default()
+            })
+            .unwrap()
+            .iter()
+            .map(|record| record.timestamp)
+            .collect::<Vec<_>>()
+    };
+    let mut pages = [page(0), page(1)].concat();
+    pages.sort();
+    assert_eq!(pages, vec![1, 3]);
+    assert!(page(2).is_empty());
+    assert_eq!(
+        timestamps(vec![(EntityKind::Url, "https://www.example.com/a?b=1")]),
+        vec![0]
+    );
+    let top = |kind| {
+        recorder
+            .top_entities(kind, &Query::default(), 10)
+            .unwrap()
+            .into_iter()
+            .map(|entity| (entity.value, entity.count))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        top(EntityKind::Domain),
+        vec![("example.com".into(), 2), ("blog.rust-lang.org".into(), 1)]
+    );
+    assert_eq!(top(EntityKind::Emoji), vec![("👍".into(), 2)]);
+    assert_eq!(
+        recorder
+            .top_entities(
+                EntityKind::Domain,
+                &Query {
+                    before: Some(0),
+                    ..Default::default()
+                },
+                10
+            )
+            .unwrap(),
+        vec![EntityCount {
+            value: "example.com".into(),
+            count: 1
+        }]
+    );
+    assert_eq!(
+        recorder
+            .top_entities(
+                EntityKind::Mention,
+                &Query {
+                    keyword: Some("明天".into()),
+                    ..Default::default()
+                },
+                10
+            )
+            .unwrap()
+            .len(),
+        2
+    );
+}
+
+#[test]
+fn test_facets() {
+    let mut recorder = test_recorder("facets");
+    for (chat_type, group, sender, content, timestamp) in [
+        ("qq", "g1", "a", "今天天气很好", 1_700_000_000_000i64),
+        ("qq", "g1", "b", "天气不错", 1_700_000_000_001),
+        ("qq", "g2", "a", "天气", 1_702_000_000_000),
+        ("wechat", "g1", "c", "天气", 1_702_000_000_001),
+        ("wechat", "g1", "c", "晚安", 1_702_000_000_002),
+    ] {
+        assert!(recorder
+            .insert_or_update_record(
+                &Record {
+                    group_id: group.into(),
+                    sender_id: sender.into(),
+                    sender_name: sender.into(),
+                    ..test_record(chat_type, content, timestamp)
+                },
+                None
+            )
+            .unwrap());
+    }
+    recorder.refresh_index().unwrap();
+    let result = recorder
+        .get_record_with_facets(
+            Query {
+                keyword: Some("天气".into()),
+                limit: Some(2),
+                ..Default::default()
+            },
+            &[
+                FacetField::ChatType,
+                FacetField::Group(None),
+                FacetField::Sender(Some("qq".into())),
+                FacetField::Month,
+            ],
+        )
+        .unwrap();
+    assert_eq!(result.records.len(), 2);
+    let facets = result
+        .facets
+        .into_iter()
+        .map(|(_, counts)| {
+            counts
+                .into_iter()
+                .map(|count| (count.path.join("/"), count.count))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        facets[0],
+        vec![("qq".to_string(), 3), ("wechat".to_string(), 1)]
+    );
+    assert_eq!(
+        facets[1],
+        vec![
+            ("qq/g1".to_string(), 2),
+            ("qq/g2".to_string(), 1),
+            ("wechat/g1".to_string(), 1)
+        ]
+    );
+    assert_eq!(
+        facets[2],
+        vec![("qq/a".to_string(), 2), ("qq/b".to_string(), 1)]
+    );
+    assert_eq!(
+        facets[3],
+        vec![("2023-11".to_string(), 2), ("2023-12".to_string(), 2)]
+    );
+
+    let result = recorder
+        .get_record_with_facets(
+            Query {
+                keyword: Some("天气".into()),
+                group_id: Some("g1".into()),
+                after: Some(1_700_000_000_001),
+                ..Default::default()
+            },
+            &[FacetField::ChatType, FacetField::Sender(None)],
+        )
+        .unwrap();
+    assert_eq!(result.records.len(), 2);
+    let facets = result
+        .facets
+        .into_iter()
+        .map(|(_, counts)| {
+            counts
+                .into_iter()
+                .map(|count| (count.path.join("/"), count.count))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        facets[0],
+        vec![("qq".to_string(), 1), ("wechat".to_string(), 1)]
+    );
+    assert_eq!(
+        facets[1],
+        vec![("qq/b".to_string(), 1), ("wechat/c".to_string(), 1)]
+    );
+}
+
+#[test]
+fn test_record_histogram() {
+    let mut recorder = test_recorder("histogram");
+    // 2024-01-01 00:00:00 utc, a monday
+    let day = 86_400_000;
+    let base = 1_704_067_200_000i64;
+    for (group, content, timestamp) in [
+        ("g1", "天气", base + 1),
+        ("g1", "天气", base + day + 1),
+        ("g2", "天气", base + day + 2),
+        ("g1", "晚安", base + 8 * day),
+        ("g1", "天气", base + 31 * day),
+    ] {
+        assert!(recorder
+            .insert_or_update_record(
+                &Record {
+                    group_id: group.into(),
+                    ..test_record("test_histogram", content, timestamp)
+                },
+                None
+            )
+            .unwrap());
+    }
+    recorder.refresh_index().unwrap();
+    let histogram = |group_id: Option<&str>, interval| {
+        recorder
+            .get_record_histogram(
+                &Query {
+                    group_id: group_id.map(Into::into),
+                    keyword: Some("天气".into()),
+                    ..Default::default()
+                },
+                interval,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|bucket| (bucket.key, bucket.count))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        histogram(None, HistogramInterval::Month(0)),
+        vec![("2024-01".into(), 3), ("2024-02".into(), 1)]
+    );
+    assert_eq!(
+        histogram(Some("g1"), HistogramInterval::Month(0)),
+        vec![("2024-01".into(), 2), ("2024-02".into(), 1)]
+    );
+    assert_eq!(
+        histogram(Some("g1"), HistogramInterval::Week(0)),
+        vec![
+            ("2024-01-01".into(), 2),
+            ("2024-01-08".into(), 0),
+            ("2024-01-15".into(), 0),
+            ("2024-01-22".into(), 0),
+            ("2024-01-29".into(), 1)
+        ]
+    );
+    // in utc-8 the first record is sent on 2023-12-31
+    assert_eq!(
+        histogram(None, HistogramInterval::Day(-8 * 3600))[..2],
+        [("2023-12-31".into(), 1), ("2024-01-01".into(), 2)]
+    );
+    let month = recorder
+        .get_record_histogram(&Query::default(), HistogramInterval::Month(8 * 3600))
+        .unwrap();
+    assert_eq!(month[0].start, base - 8 * 3600 * 1000);
+    assert_eq!(month[0].count, 4);
+}
+
+#[test]
+fn test_similar_records() {
+    let mut recorder = test_recorder("similar");
+    let embedder = |content: &str| {
+        let words = content.split_whitespace().collect::<Vec<_>>();
+        if words.contains(&"bird") {
+            return Some(vec![1.0; 4]);
+        }
+        Some(
+            ["cat", "dog", "car"]
+                .iter()
+                .map(|word| words.iter().filter(|w| *w == word).count() as f32)
+                .collect::<Vec<_>>(),
+        )
+    };
+    let records = [
+        ("g1", "cat cat dog"),
+        ("g1", "cat"),
+        ("g2", "dog dog"),
+        ("g1", "car"),
+        ("g1", "hello"),
+        ("g2", "bird"),
+    ]
+    .iter()
+    .enumerate()
+    .map(|(i, (group, content))| Record {
+        group_id: group.to_string(),
+        ..test_record("test_similar", content, i as i64)
+    })
+    .collect::<Vec<_>>();
+    for (i, record) in records.iter().enumerate() {
+        if i == 2 {
+            recorder.set_embedder(embedder);
+        }
+        assert!(recorder.insert_or_update_record(record, None).unwrap());
+    }
+    // the first two records are inserted before the embedder is set,
+    // the zero vector of hello and the four dimensional vector of bird are not stored
+    assert_eq!(recorder.embed_records().unwrap(), 2);
+    recorder.refresh_index().unwrap();
+    let query = |group_id: Option<&str>, limit| Query {
+        chat_type: Some("test_similar".into()),
+        group_id: group_id.map(Into::into),
+        limit: Some(limit),
+        ..Default::default()
+    };
+    let timestamps = |result: Vec<ScoredRecord>| {
+        result
+            .into_iter()
+            .map(|scored| scored.record.timestamp)
+            .collect::<Vec<_>>()
+    };
+    let similar = recorder
+        .get_similar_records(&records[1], &query(None, 1))
+        .unwrap();
+    assert_eq!(timestamps(similar.clone()), vec![0]);
+    assert!((similar[0].score - 2.0 / 5f32.sqrt()).abs() < 1e-4);
+    assert_eq!(
+        timestamps(
+            recorder
+                .get_similar_records(&records[1], &query(Some("g2"), 10))
+                .unwrap()
+        ),
+        vec![2]
+    );
+    assert_eq!(
+        timestamps(recorder.search_similar("dog", &query(None, 2)).unwrap()),
+        vec![2, 0]
+    );
+    assert!(recorder
+        .search_similar("hello", &query(None, 2))
+        .unwrap()
+        .is_empty());
+    let mut hybrid = timestamps(recorder.hybrid_search("cat", &query(None, 2)).unwrap());
+    hybrid.sort();
+    assert_eq!(hybrid, vec![0, 1]);
+    assert_eq!(
+        timestamps(recorder.hybrid_search("hello", &query(None, 1)).unwrap()),
+        vec![4]
+    );
+}
+
+#[test]
+fn test_more_like_this() {
+    let mut recorder = test_recorder("more_like_this");
+    let records = [
+        (
+            "g1",
+            "urgent notice water outage tomorrow please store water",
+        ),
+        (
+            "g2",
+            "fwd urgent notice water outage tomorrow please store water",
+        ),
+        ("g3", "water outage notice"),
+        ("g1", "nice weather today"),
+    ]
+    .iter()
+    .enumerate()
+    .map(|(i, (group, content))| Record {
+        group_id: group.to_string(),
+        ..test_record("test_more_like_this", content, i as i64)
+    })
+    .collect::<Vec<_>>();
+    for record in records.iter() {
+        assert!(recorder.insert_or_update_record(record, None).unwrap());
+    }
+    recorder.refresh_index().unwrap();
+    let timestamps = |group_id: Option<&str>| {
+        recorder
+            .get_more_like_this(
+                &records[0],
+                &Query {
+                    chat_type: Some("test_more_like_this".into()),
+                    group_id: group_id.map(Into::into),
+                    ..Default::default()
+                },
+                &Default::default(),
+            )
+            .unwrap()
+            .iter()
+            .map(|record| record.timestamp)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(timestamps(None), vec![1, 2]);
+    assert_eq!(timestamps(Some("g3")), vec![2]);
+    assert!(timestamps(Some("g1")).is_empty());
+}
+
+#[test]
+fn test_duplicates() {
+    let mut recorder = test_recorder("duplicates");
+    let rumor = "The quick brown fox jumps over the lazy dog near the river bank";
+    let records = [
+        ("g1", rumor.to_string(), 100),
+        ("g2", rumor.to_uppercase(), 200),
+        ("g3", format!("{}!!!", rumor), 50),
+        ("g1", rumor.replace(' ', ""), 300),
+        (
+            "g1",
+            "a completely different message about the weather".into(),
+            400,
+        ),
+        ("g2", "ok".into(), 500),
+    ]
+    .iter()
+    .map(|(group, content, timestamp)| Record {
+        group_id: group.to_string(),
+        ..test_record("test_duplicates", content, *timestamp)
+    })
+    .collect::<Vec<_>>();
+    for record in records.iter() {
+        assert!(recorder.insert_or_update_record(record, None).unwrap());
+    }
+    let timestamps = |cluster: &DuplicateCluster| {
+        cluster
+            .records
+            .iter()
+            .map(|record| record.timestamp)
+            .collect::<Vec<_>>()
+    };
+    let cluster = recorder.get_duplicates(&records[0], 3).unwrap();
+    assert_eq!(timestamps(&cluster), vec![50, 100, 200, 300]);
+    assert_eq!(cluster.earliest().unwrap().group_id, "g3");
+    assert_eq!(
+        cluster
+            .spread()
+            .into_iter()
+            .map(|spread| (spread.group_id, spread.first, spread.count))
+            .collect::<Vec<_>>(),
+        vec![
+            ("g3".into(), 50, 1),
+            ("g1".into(), 100, 2),
+            ("g2".into(), 200, 1)
+        ]
+    );
+    assert_eq!(
+        timestamps(&recorder.get_duplicates(&records[5], 3).unwrap()),
+        vec![500]
+    );
+    let clusters = recorder
+        .cluster_duplicates(
+            &Query {
+                chat_type: Some("test_duplicates".into()),
+                ..Default::default()
+            },
+            3,
+            None,
+        )
+        .unwrap();
+    assert_eq!(clusters.len(), 1);
+    assert_eq!(timestamps(&clusters[0]), vec![50, 100, 200, 300]);
+    assert!(recorder
+        .cluster_duplicates(
+            &Query {
+                chat_type: Some("test_duplicates".into()),
+                ..Default::default()
+            },
+            3,
+            Some(0),
+        )
+        .unwrap()
+        .is_empty());
+}
+
+#[cfg(feature = "pdf")]
+#[test]
+fn test_truncated_pdf() {
+    let mut recorder = test_recorder("truncated_pdf");
+    let truncated = b"%PDF-1.7\n1 0 obj\n<< /Type /Catalog /Pages 2 0 R".to_vec();
+    assert_eq!(PdfExtractor.extract(&truncated), None);
+    assert!(recorder
+        .insert_or_update_record(
+            (
+                &test_record("test_truncated_pdf", "see attached", 1),
+                vec![("broken.pdf".to_string(), truncated)]
+                    .into_iter()
+                    .collect()
+            ),
+            None
+        )
+        .unwrap());
+    // the failed extraction is cached, so refreshing does not extract it again
+    recorder.refresh_index().unwrap();
+    recorder.refresh_index().unwrap();
+    assert!(recorder
+        .search_attachments(&Query {
+            keyword: Some("Catalog".into()),
+            ..Default::default()
+        })
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_attachment_search() {
+    let mut recorder = test_recorder("attachment_search");
+    let html = "<html><style>p { color: red }</style><script>var secret = 1;</script>\
+                <p>Quarterly&nbsp;report &amp; <b>forecast</b></p></html>";
+    assert_eq!(
+        HtmlExtractor.extract(html.as_bytes()),
+        Some("Quarterly report & forecast".into())
+    );
+    for (group, attachs) in [
+        (
+            "g1",
+            vec![
+                ("notes.txt", b"meeting agenda budget review".to_vec()),
+                ("page.HTML", html.as_bytes().to_vec()),
+                ("image.png", vec![0x89, b'P', b'N', b'G']),
+            ],
+        ),
+        ("g2", vec![("plan.md", b"holiday budget plan".to_vec())]),
+    ] {
+        assert!(recorder
+            .insert_or_update_record(
+                (
+                    &Record {
+                        group_id: group.into(),
+                        ..test_record("test_attachment_search", "see attached", 1)
+                    },
+                    attachs
+                        .into_iter()
+                        .map(|(name, data)| (name.to_string(), data))
+                        .collect()
+                ),
+                None
+            )
+            .unwrap());
+    }
+    recorder.refresh_index().unwrap();
+    let search = |keyword: &str, group_id: Option<&str>| {
+        let mut hits = recorder
+            .search_attachments(&Query {
+                chat_type: Some("test_attachment_search".into()),
+                group_id: group_id.map(Into::into),
+                keyword: Some(keyword.into()),
+                ..Default::default()
+            })
+            .unwrap()
+            .into_iter()
+            .map(|hit| format!("{}/{}", hit.record.group_id, hit.attachment.name))
+            .collect::<Vec<_>>();
+        hits.sort();
+        hits
+    };
+    assert_eq!(search("budget", None), vec!["g1/notes.txt", "g2/plan.md"]);
+    assert_eq!(search("budget", Some("g2")), vec!["g2/plan.md"]);
+    assert_eq!(search("forecast", None), vec!["g1/page.HTML"]);
+    assert!(search("secret", None).is_empty());
+    // attachment text is not part of the record search
+    assert_eq!(
+        recorder
+            .count_records(&Query {
+                keyword: Some("budget".into()),
+                ..Default::default()
+            })
+            .unwrap(),
+        0
+    );
+}
+
+#[test]
+fn test_metadata() {
+    use serde_json::json;
+    let mut recorder = test_recorder("metadata");
+    let metadata = [
+        Some(json!({"type": "forward", "reply": {"id": 7}, "tags": ["urgent", "water"]})),
+        Some(json!({"type": "text", "reply": {"id": 3}})),
+        Some(json!({"type": "forward", "tags": []})),
+        None,
+    ];
+    for (i, metadata) in metadata.iter().enumerate() {
+        let mut record = test_record("test_metadata", "hello", i as i64);
+        if let Some(metadata) = metadata {
+            record.set_metadata(MetadataCodec::Json, metadata).unwrap();
+            assert_eq!(
+                record
+                    .get_metadata::<serde_json::Value>(MetadataCodec::Json)
+                    .unwrap()
+                    .as_ref(),
+                Some(metadata)
+            );
+        }
+        assert!(recorder.insert_or_update_record(&record, None).unwrap());
+    }
+    // metadata which is not json is skipped by the filters
+    assert!(recorder
+        .insert_or_update_record(
+            &Record {
+                metadata: Some(vec![0xff, 0x00]),
+                ..test_record("test_metadata", "hello", 4)
+            },
+            None
+        )
+        .unwrap());
+    let timestamps = |metadata: Vec<MetadataFilter>| {
+        recorder
+            .get_record(Query {
+                chat_type: Some("test_metadata".into()),
+                metadata,
+                ..Default::default()
+            })
+            .unwrap()
+            .iter()
+            .map(|record| record.timestamp)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        timestamps(vec![MetadataFilter::Eq("$.type".into(), json!("forward"))]),
+        vec![0, 2]
+    );
+    assert_eq!(
+        timestamps(vec![MetadataFilter::Gt("$.reply.id".into(), json!(5))]),
+        vec![0]
+    );
+    assert_eq!(
+        timestamps(vec![
+            MetadataFilter::Exists("$.reply".into()),
+            MetadataFilter::Eq("$.type".into(), json!("text")).negate()
+        ]),
+        vec![0]
+    );
+    assert_eq!(
+        timestamps(vec![MetadataFilter::In(
+            "$.tags[0]".into(),
+            vec![json!("urgent"), json!("other")]
+        )]),
+        vec![0]
+    );
+    assert_eq!(
+        timestamps(vec![MetadataFilter::Exists("$.reply".into()).negate()]),
+        vec![2, 3, 4]
+    );
+    recorder
+        .set_indexed_metadata(MetadataCodec::Json, &["type".into(), "tags".into()])
+        .unwrap();
+    let count = |keyword: &str| {
+        recorder
+            .count_records(&Query {
+                chat_type: Some("test_metadata".into()),
+                keyword: Some(keyword.into()),
+                ..Default::default()
+            })
+            .unwrap()
+    };
+    assert_eq!(count("forward"), 2);
+    assert_eq!(count("meta_tags:water"), 1);
+    assert_eq!(count("meta_type:water"), 0);
+}
+
+#[test]
+fn test_metadata_mergers() {
+    use serde_json::{json, Value};
+    let mut recorder = test_recorder("merger");
+    let mut merge = |timestamp: i64, merger: &mut dyn MetadataMerger<SqliteChatRecorder>| {
+        let mut record = test_record("test_merger", "hello", timestamp);
+        for metadata in [
+            json!({"name": "old", "tags": ["a"], "reply": {"id": 1, "seen": true}}),
+            json!({"name": "", "tags": ["a", "b"], "reply": {"id": 2}}),
+        ] {
+            record.set_metadata(MetadataCodec::Json, &metadata).unwrap();
+            assert!(recorder
+                .insert_or_update_record(&record, Some(&mut *merger))
+                .unwrap());
+        }
+        recorder
+            .get_record(Query {
+                chat_type: Some("test_merger".into()),
+                ..Default::default()
+            })
+            .unwrap()
+            .into_iter()
+            .find(|record| record.timestamp == timestamp)
+            .unwrap()
+            .get_metadata::<Value>(MetadataCodec::Json)
+            .unwrap()
+            .unwrap()
+    };
+    assert_eq!(
+        merge(0, &mut keep_old_metadata),
+        json!({"name": "old", "tags": ["a"], "reply": {"id": 1, "seen": true}})
+    );
+    assert_eq!(
+        merge(1, &mut keep_new_metadata),
+        json!({"name": "", "tags": ["a", "b"], "reply": {"id": 2}})
+    );
+    assert_eq!(
+        merge(2, &mut deep_merge_metadata),
+        json!({"name": "", "tags": ["a", "b"], "reply": {"id": 2, "seen": true}})
+    );
+    assert_eq!(
+        merge(3, &mut union_metadata),
+        json!({"name": "", "tags": ["a", "b"], "reply": {"id": 2, "seen": true}})
+    );
+    assert_eq!(
+        merge(4, &mut prefer_non_empty_metadata),
+        json!({"name": "", "tags": ["a", "b"], "reply": {"id": 2}})
+    );
+    let per_key = JsonMerger::new(JsonMerge::KeepNew)
+        .key("name", JsonMerge::PreferNonEmpty)
+        .key("reply", JsonMerge::Deep)
+        .key("reply.id", JsonMerge::KeepOld);
+    assert_eq!(
+        merge(5, &mut |_: &SqliteChatRecorder,
+                       _: &Attachments,
+                       old,
+                       new| {
+            per_key.merge(MetadataCodec::Json, old, new)
+        }),
+        json!({"name": "old", "tags": ["a", "b"], "reply": {"id": 1, "seen": true}})
+    );
+    // keys with their own strategy are merged without a strategy for their parents
+    assert_eq!(
+        JsonMerger::new(JsonMerge::KeepNew)
+            .key("reply.id", JsonMerge::KeepOld)
+            .merge_value(
+                json!({"name": "old", "reply": {"id": 1, "seen": true}}),
+                json!({"name": "", "tags": ["a"], "reply": {"id": 2}})
+            ),
+        json!({"name": "", "tags": ["a"], "reply": {"id": 1}})
+    );
+    // mergers can keep state and report conflicts
+    let mut conflicts = 0;
+    let mut merger = |_: &SqliteChatRecorder, _: &Attachments, old: Vec<u8>, new: Vec<u8>| {
+        if old == new {
+            MergedMetadata::Merged(new)
+        } else {
+            conflicts += 1;
+            MergedMetadata::Conflict("metadata changed".into())
+        }
+    };
+    let record = Record {
+        sender_name: "renamed".into(),
+        metadata: Some(b"{}".to_vec()),
+        ..test_record("test_merger", "hello", 0)
+    };
+    assert!(matches!(
+        recorder.insert_or_update_record(&record, Some(&mut merger)),
+        Err(ChatRecordError::MetadataConflict(_))
+    ));
+    assert_eq!(conflicts, 1);
+    // the sender alias of the conflicting record is rolled back
+    assert!(recorder
+        .get_sender_aliases("test_merger", "sender")
+        .unwrap()
+        .iter()
+        .all(|alias| alias.name == "sender"));
+    assert_eq!(
+        recorder
+            .get_record(Query {
+                chat_type: Some("test_merger".into()),
+                ..Default::default()
+            })
+            .unwrap()[0]
+            .get_metadata::<Value>(MetadataCodec::Json)
+            .unwrap(),
+        Some(json!({"name": "old", "tags": ["a"], "reply": {"id": 1, "seen": true}}))
+    );
+    // metadata which is not json is replaced
+    assert_eq!(
+        JsonMerger::new(JsonMerge::Deep).merge(MetadataCodec::Json, vec![0xff], b"[1]".to_vec()),
+        Some(b"[1]".to_vec())
+    );
+}
+
+#[test]
+fn test_attachment_policy() {
+    let mut recorder = test_recorder("attachment_policy");
+    let attachs = |attachs: &[(&str, &str)]| {
+        attachs
+            .iter()
+            .map(|(name, data)| (name.to_string(), data.as_bytes().to_vec()))
+            .collect::<Attachments>()
+    };
+    let names = |names: &[&str]| {
+        names
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>()
+    };
+    for (timestamp, policy, report, stored) in [
+        (
+            0,
+            AttachmentPolicy::ReplaceAll,
+            AttachmentReport {
+                added: names(&["c.txt"]),
+                replaced: names(&["a.txt"]),
+                unchanged: names(&["b.txt"]),
+                removed: names(&["d.txt"]),
+                ..Default::default()
+            },
+            names(&["a.txt", "b.txt", "c.txt"]),
+        ),
+        (
+            1,
+            AttachmentPolicy::MergeByName,
+            AttachmentReport {
+                added: names(&["c.txt"]),
+                replaced: names(&["a.txt"]),
+                unchanged: names(&["b.txt"]),
+                ..Default::default()
+            },
+            names(&["a.txt", "b.txt", "c.txt", "d.txt"]),
+        ),
+        (
+            2,
+            AttachmentPolicy::KeepExisting,
+            AttachmentReport {
+                added: names(&["c.txt"]),
+                unchanged: names(&["a.txt", "b.txt"]),
+                ..Default::default()
+            },
+            names(&["a.txt", "b.txt", "c.txt", "d.txt"]),
+        ),
+        (
+            3,
+            AttachmentPolicy::AppendVersioned,
+            AttachmentReport {
+                added: names(&["a (1).txt", "c.txt"]),
+                unchanged: names(&["b.txt"]),
+                ..Default::default()
+            },
+            names(&["a (1).txt", "a.txt", "b.txt", "c.txt", "d.txt"]),
+        ),
+    ] {
+        recorder.set_attachment_policy(policy);
+        let record = test_record("test_attachment_policy", "see attached", timestamp);
+        assert_eq!(
+            recorder
+                .insert_or_update_record_with_report(
+                    (
+                        &record,
+                        attachs(&[("a.txt", "a"), ("b.txt", "b"), ("d.txt", "d")])
+                    ),
+                    None
+                )
+                .unwrap(),
+            Some(AttachmentReport {
+                added: names(&["a.txt", "b.txt", "d.txt"]),
+                ..Default::default()
+            })
+        );
+        let update = attachs(&[("a.txt", "a2"), ("b.txt", "b"), ("c.txt", "c")]);
+        assert_eq!(
+            recorder
+                .insert_or_update_record_with_report((&record, update.clone()), None)
+                .unwrap(),
+            Some(report)
+        );
+        let record_id = recorder
+            .get_record(Query {
+                chat_type: Some("test_attachment_policy".into()),
+                ..Default::default()
+            })
+            .unwrap()
+            .into_iter()
+            .find(|record| record.timestamp == timestamp)
+            .unwrap()
+            .get_id();
+        let mut attachments = recorder
+            .get_attachments(record_id)
+            .unwrap()
+            .into_iter()
+            .map(|attach| attach.name)
+            .collect::<Vec<_>>();
+        attachments.sort();
+        assert_eq!(attachments, stored);
+        // importing the same attachments again changes nothing
+        let report = recorder
+            .insert_or_update_record_with_report((&record, update), None)
+            .unwrap()
+            .unwrap();
+        assert!(report.added.is_empty() && report.replaced.is_empty() && report.removed.is_empty());
+    }
+    // records of different senders at the same time keep their own attachments
+    for sender in ["alice", "bob"] {
+        let record = Record {
+            sender_id: sender.into(),
+            ..test_record("test_attachment_sender", "see attached", 0)
+        };
+        assert!(recorder
+            .insert_or_update_record((&record, attachs(&[(sender, sender)])), None)
+            .unwrap());
+    }
+    for record in recorder
+        .get_record(Query {
+            chat_type: Some("test_attachment_sender".into()),
+            ..Default::default()
+        })
+        .unwrap()
+    {
+        let attachments = recorder.get_attachments(record.get_id()).unwrap();
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].name, record.sender_id);
+    }
 }
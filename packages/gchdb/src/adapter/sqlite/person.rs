@@ -0,0 +1,209 @@
+use super::*;
+
+fn update_person(conn: &mut SqliteConnection, person: &Person) -> ChatRecordResult<usize> {
+    use schema::persons::dsl::*;
+    Ok(update(persons.filter(id.eq(person.id)))
+        .set((name.eq(&person.name), metadata.eq(&person.metadata)))
+        .execute(conn)?)
+}
+
+fn insert_person(conn: &mut SqliteConnection, person: &Person) -> ChatRecordResult<i32> {
+    use diesel::{dsl::sql, sql_types::Integer};
+    Ok(
+        if insert_into(persons::table).values(person).execute(conn)? == 1 {
+            select(sql::<Integer>("last_insert_rowid()")).get_result(conn)?
+        } else {
+            0
+        },
+    )
+}
+
+/// returns the id of the person, a new person is inserted if `person.id` is none
+pub fn insert_or_update_person(
+    conn: &mut SqliteConnection,
+    person: &Person,
+) -> ChatRecordResult<i32> {
+    Ok(
+        if person.id.is_some() && update_person(conn, person)? == 1 {
+            person.get_id()
+        } else {
+            insert_person(conn, person)?
+        },
+    )
+}
+
+pub fn get_person(conn: &mut SqliteConnection, person_id: i32) -> ChatRecordResult<Option<Person>> {
+    use schema::persons::dsl::*;
+    Ok(persons
+        .filter(id.eq(person_id))
+        .get_result(conn)
+        .optional()?)
+}
+
+pub fn remove_person(conn: &mut SqliteConnection, person_id: i32) -> ChatRecordResult<usize> {
+    use schema::persons::{dsl::*, table};
+    delete(person_identities::table)
+        .filter(person_identities::person_id.eq(person_id))
+        .execute(conn)?;
+    Ok(delete(table).filter(id.eq(person_id)).execute(conn)?)
+}
+
+fn check_identity(
+    conn: &mut SqliteConnection,
+    identity: &PersonIdentity,
+) -> ChatRecordResult<bool> {
+    use schema::person_identities::dsl::*;
+    Ok(select(exists(
+        person_identities.filter(
+            chat_type
+                .eq(&identity.chat_type)
+                .and(sender_id.eq(&identity.sender_id)),
+        ),
+    ))
+    .get_result(conn)
+    .unwrap_or(false))
+}
+
+fn update_identity(
+    conn: &mut SqliteConnection,
+    identity: &PersonIdentity,
+) -> ChatRecordResult<usize> {
+    use schema::person_identities::dsl::*;
+    Ok(update(
+        person_identities.filter(
+            chat_type
+                .eq(&identity.chat_type)
+                .and(sender_id.eq(&identity.sender_id)),
+        ),
+    )
+    .set(person_id.eq(identity.person_id))
+    .execute(conn)?)
+}
+
+fn insert_identity(
+    conn: &mut SqliteConnection,
+    identity: &PersonIdentity,
+) -> ChatRecordResult<usize> {
+    Ok(insert_into(person_identities::table)
+        .values(identity)
+        .execute(conn)?)
+}
+
+/// link the identity to the person, an identity already linked to another person is moved
+pub fn link_identity(
+    conn: &mut SqliteConnection,
+    identity: &PersonIdentity,
+) -> ChatRecordResult<bool> {
+    Ok(if check_identity(conn, identity)? {
+        update_identity(conn, identity)
+    } else {
+        insert_identity(conn, identity)
+    }? == 1)
+}
+
+pub fn unlink_identity(
+    conn: &mut SqliteConnection,
+    identity: &PersonIdentity,
+) -> ChatRecordResult<usize> {
+    use schema::person_identities::{dsl::*, table};
+    Ok(delete(table)
+        .filter(
+            person_id.eq(identity.person_id).and(
+                chat_type
+                    .eq(&identity.chat_type)
+                    .and(sender_id.eq(&identity.sender_id)),
+            ),
+        )
+        .execute(conn)?)
+}
+
+pub fn get_identities(
+    conn: &mut SqliteConnection,
+    identity_person_id: i32,
+) -> ChatRecordResult<Vec<PersonIdentity>> {
+    use schema::person_identities::dsl::*;
+    Ok(person_identities
+        .filter(person_id.eq(identity_person_id))
+        .load(conn)?)
+}
+
+pub fn get_person_by_identity(
+    conn: &mut SqliteConnection,
+    identity_chat_type: &str,
+    identity_sender_id: &str,
+) -> ChatRecordResult<Option<Person>> {
+    use schema::person_identities::dsl::*;
+    let identity_person_id = person_identities
+        .filter(
+            chat_type
+                .eq(identity_chat_type)
+                .and(sender_id.eq(identity_sender_id)),
+        )
+        .select(person_id)
+        .get_result::<i32>(conn)
+        .optional()?;
+    Ok(match identity_person_id {
+        Some(identity_person_id) => get_person(conn, identity_person_id)?,
+        None => None,
+    })
+}
+
+#[test]
+fn test_person() {
+    let mut recorder = test_recorder("person");
+    let person_id = recorder
+        .insert_or_update_person(&Person {
+            name: "张三".into(),
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(person_id > 0);
+    for (chat_type, sender_id) in [("qq", "10000"), ("wechat", "wxid_zhangsan")] {
+        assert!(recorder
+            .link_identity(&PersonIdentity {
+                id: None,
+                person_id,
+                chat_type: chat_type.into(),
+                sender_id: sender_id.into(),
+            })
+            .unwrap());
+        assert!(recorder
+            .insert_or_update_record(
+                &Record {
+                    sender_id: sender_id.into(),
+                    sender_name: "张三".into(),
+                    ..test_record(chat_type, "hello", 1)
+                },
+                None
+            )
+            .unwrap());
+    }
+    assert!(recorder
+        .insert_or_update_record(
+            &Record {
+                sender_id: "10001".into(),
+                sender_name: "李四".into(),
+                ..test_record("qq", "hello", 1)
+            },
+            None
+        )
+        .unwrap());
+    assert_eq!(recorder.get_identities(person_id).unwrap().len(), 2);
+    assert_eq!(
+        recorder
+            .get_person_by_identity("qq", "10000")
+            .unwrap()
+            .unwrap()
+            .name,
+        "张三"
+    );
+    let records = recorder
+        .get_record(Query {
+            person_id: Some(person_id),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(records.len(), 2);
+    assert!(recorder.remove_person(person_id).unwrap());
+    assert!(recorder.get_identities(person_id).unwrap().is_empty());
+}
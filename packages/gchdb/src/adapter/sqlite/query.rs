@@ -0,0 +1,457 @@
+use super::*;
+use diesel::{
+    dsl::{not, sql},
+    expression::{is_aggregate, BoxableExpression, ValidGrouping},
+    query_builder::QueryFragment,
+    sql_types::{Bool, Text},
+    sqlite::Sqlite,
+};
+
+pub type RecordPredicate<'a> =
+    Box<dyn BoxableExpression<records::table, Sqlite, SqlType = Bool> + 'a>;
+
+/// records in the time range of the query and linked to the person of the query
+pub fn query_scope(query: &Query) -> RecordPredicate<'_> {
+    use schema::records::dsl::*;
+    let mut scope: RecordPredicate = Box::new(
+        timestamp
+            .le(query.before.unwrap_or_else(get_now))
+            .and(timestamp.ge(query.after.unwrap_or(0))),
+    );
+    if let Some(person_id) = query.person_id {
+        scope = Box::new(scope.and(person_filter(person_id)));
+    }
+    scope
+}
+
+/// records sent by any identity linked to the person
+pub fn person_filter<'a>(person_id: i32) -> RecordPredicate<'a> {
+    use schema::records::dsl::*;
+    Box::new(exists(
+        person_identities::table.filter(
+            person_identities::person_id
+                .eq(person_id)
+                .and(person_identities::chat_type.eq(chat_type))
+                .and(person_identities::sender_id.eq(sender_id)),
+        ),
+    ))
+}
+
+/// ids are inlined instead of bound, so large keyword results don't hit the variable limit of sqlite
+pub fn ids_filter<'a>(ids: &[i32]) -> RecordPredicate<'a> {
+    Box::new(sql::<Bool>(&if ids.is_empty() {
+        "0".into()
+    } else {
+        format!(
+            "records.id IN ({})",
+            ids.iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(",")
+        )
+    }))
+}
+
+/// records with at least one attachment
+pub fn attachment_filter<'a>() -> RecordPredicate<'a> {
+    use schema::records::dsl::*;
+    Box::new(exists(
+        attachments::table.filter(attachments::record_id.nullable().eq(id)),
+    ))
+}
+
+fn quote(src: &str) -> String {
+    format!("'{}'", src.replace('\'', "''"))
+}
+
+fn metadata_sql(filter: &MetadataFilter) -> String {
+    const METADATA: &str = "CAST(records.metadata AS TEXT)";
+    let value = |path: &str| {
+        format!(
+            "(CASE WHEN json_valid({0}) THEN json_extract({0}, {1}) END)",
+            METADATA,
+            quote(path)
+        )
+    };
+    let json =
+        |value: &serde_json::Value| format!("json_extract({}, '$')", quote(&value.to_string()));
+    match filter {
+        MetadataFilter::Exists(path) => format!("{} IS NOT NULL", value(path)),
+        MetadataFilter::Eq(path, other) => format!("{} = {}", value(path), json(other)),
+        MetadataFilter::Gt(path, other) => format!("{} > {}", value(path), json(other)),
+        MetadataFilter::Lt(path, other) => format!("{} < {}", value(path), json(other)),
+        MetadataFilter::In(_, values) if values.is_empty() => "0".into(),
+        MetadataFilter::In(path, values) => format!(
+            "{} IN ({})",
+            value(path),
+            values.iter().map(json).collect::<Vec<_>>().join(", ")
+        ),
+        MetadataFilter::Not(filter) => format!("NOT ({})", metadata_sql(filter)),
+    }
+}
+
+/// paths and values are inlined as quoted literals, metadata which is not valid json is skipped
+/// instead of failing the whole query
+pub fn metadata_filter<'a>(filter: &MetadataFilter) -> RecordPredicate<'a> {
+    Box::new(sql::<Bool>(&metadata_sql(filter)))
+}
+
+/// records in the same conversation as the record
+pub fn conversation_filter(record: &Record) -> RecordPredicate<'_> {
+    use schema::records::dsl::*;
+    Box::new(
+        chat_type
+            .eq(&record.chat_type)
+            .and(owner_id.eq(&record.owner_id))
+            .and(group_id.eq(&record.group_id)),
+    )
+}
+
+pub fn string_filter<'a, C>(column: C, filter: &'a StringFilter) -> RecordPredicate<'a>
+where
+    C: Column<Table = records::table, SqlType = Text>
+        + SelectableExpression<records::table>
+        + QueryFragment<Sqlite>
+        + ValidGrouping<(), IsAggregate = is_aggregate::No>
+        + Send
+        + 'a,
+{
+    match filter {
+        StringFilter::Exact(value) => Box::new(column.eq(value)),
+        StringFilter::Prefix(prefix) => Box::new(
+            column
+                .like(format!("{}%", StringFilter::escape_like(prefix)))
+                .escape('\\'),
+        ),
+        StringFilter::Like(pattern) => Box::new(column.like(pattern).escape('\\')),
+        StringFilter::In(values) => Box::new(column.eq_any(values)),
+        StringFilter::Not(filter) => Box::new(not(string_filter(column, filter))),
+    }
+}
+
+/// records matched by all the filters of the query except the keyword
+pub fn query_filter(query: &Query) -> RecordPredicate<'_> {
+    use schema::records::dsl::*;
+    let mut filter = query_scope(query);
+    if let Some(value) = &query.chat_type {
+        filter = Box::new(filter.and(string_filter(chat_type, value)));
+    }
+    if let Some(value) = &query.owner_id {
+        filter = Box::new(filter.and(string_filter(owner_id, value)));
+    }
+    if let Some(value) = &query.group_id {
+        filter = Box::new(filter.and(string_filter(group_id, value)));
+    }
+    if let Some(value) = &query.sender_id {
+        filter = Box::new(filter.and(string_filter(sender_id, value)));
+    }
+    if let Some(value) = &query.sender_name {
+        filter = Box::new(filter.and(string_filter(sender_name, value)));
+    }
+    if let Some(ids) = &query.ids {
+        filter = Box::new(filter.and(ids_filter(ids)));
+    }
+    if let Some(has_attachments) = query.has_attachments {
+        filter = if has_attachments {
+            Box::new(filter.and(attachment_filter()))
+        } else {
+            Box::new(filter.and(not(attachment_filter())))
+        };
+    }
+    for value in &query.metadata {
+        filter = Box::new(filter.and(metadata_filter(value)));
+    }
+    filter
+}
+
+pub fn sort_query(
+    query: records::BoxedQuery<'_, Sqlite>,
+    sort: QuerySort,
+) -> records::BoxedQuery<'_, Sqlite> {
+    use schema::records::dsl::*;
+    match sort {
+        QuerySort::TimestampAsc | QuerySort::Relevance => query.order((timestamp.asc(), id.asc())),
+        QuerySort::TimestampDesc => query.order((timestamp.desc(), id.desc())),
+        QuerySort::IdAsc => query.order(id.asc()),
+        QuerySort::IdDesc => query.order(id.desc()),
+    }
+}
+
+/// restore the order of ids returned by the indexer, which is lost after loading from database
+pub fn sort_by_ids(mut result: Vec<Record>, ids: &[i32]) -> Vec<Record> {
+    let positions = ids
+        .iter()
+        .enumerate()
+        .map(|(position, id)| (*id, position))
+        .collect::<HashMap<_, _>>();
+    result.sort_by_key(|record| positions.get(&record.get_id()).copied());
+    result
+}
+
+/// records strictly after or before the cursor in `(timestamp, id)` order
+pub fn cursor_filter<'a>(cursor: &Cursor) -> RecordPredicate<'a> {
+    use schema::records::dsl::*;
+    match cursor.direction {
+        CursorDirection::After => Box::new(
+            timestamp.gt(cursor.timestamp).or(timestamp
+                .eq(cursor.timestamp)
+                .and(id.assume_not_null().gt(cursor.id))),
+        ),
+        CursorDirection::Before => Box::new(
+            timestamp.lt(cursor.timestamp).or(timestamp
+                .eq(cursor.timestamp)
+                .and(id.assume_not_null().lt(cursor.id))),
+        ),
+    }
+}
+
+/// load a page of records from the cursor, records are in ascending order,
+/// one more record than the limit is fetched to check if there are more records
+pub fn load_page(
+    conn: &mut SqliteConnection,
+    filter: RecordPredicate,
+    cursor: &Cursor,
+    limit: i64,
+) -> ChatRecordResult<(Vec<Record>, bool)> {
+    use schema::records::dsl::*;
+    let filtered = records.filter(filter).filter(cursor_filter(cursor));
+    let mut result = match cursor.direction {
+        CursorDirection::After => filtered
+            .order((timestamp.asc(), id.asc()))
+            .limit(limit + 1)
+            .load::<Record>(conn)?,
+        CursorDirection::Before => filtered
+            .order((timestamp.desc(), id.desc()))
+            .limit(limit + 1)
+            .load::<Record>(conn)?,
+    };
+    let has_more = result.len() as i64 > limit;
+    result.truncate(limit as usize);
+    if cursor.direction == CursorDirection::Before {
+        result.reverse();
+    }
+    Ok((result, has_more))
+}
+
+/// whether any record matched by the filter is strictly after or before the cursor
+pub fn has_records(
+    conn: &mut SqliteConnection,
+    filter: RecordPredicate,
+    cursor: &Cursor,
+) -> ChatRecordResult<bool> {
+    use schema::records::dsl::*;
+    Ok(select(exists(records.filter(filter).filter(cursor_filter(cursor)))).get_result(conn)?)
+}
+
+#[test]
+fn test_record_page() {
+    let mut recorder = test_recorder("page");
+    for i in 0..5 {
+        assert!(recorder
+            .insert_or_update_record(
+                &Record {
+                    sender_id: i.to_string(),
+                    sender_name: i.to_string(),
+                    ..test_record("test_page", "hello", i / 2)
+                },
+                None
+            )
+            .unwrap());
+    }
+    let query = Query {
+        chat_type: Some("test_page".into()),
+        limit: Some(2),
+        ..Default::default()
+    };
+    let senders = |page: &RecordPage| {
+        page.records
+            .iter()
+            .map(|record| record.sender_id.as_str())
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    let first = recorder.get_record_page(query.clone()).unwrap();
+    assert_eq!(senders(&first), "0,1");
+    assert_eq!(first.prev, None);
+    let cursor = first.next.unwrap().to_string().parse::<Cursor>().unwrap();
+    let second = recorder
+        .get_record_page(Query {
+            cursor: Some(cursor),
+            ..query.clone()
+        })
+        .unwrap();
+    assert_eq!(senders(&second), "2,3");
+    let third = recorder
+        .get_record_page(Query {
+            cursor: second.next,
+            ..query.clone()
+        })
+        .unwrap();
+    assert_eq!(senders(&third), "4");
+    assert_eq!(third.next, None);
+    let back = recorder
+        .get_record_page(Query {
+            cursor: third.prev,
+            ..query.clone()
+        })
+        .unwrap();
+    assert_eq!(senders(&back), "2,3");
+    let newest = recorder
+        .get_record_page(Query {
+            cursor: Some(Cursor::newest()),
+            ..query
+        })
+        .unwrap();
+    assert_eq!(senders(&newest), "3,4");
+    assert_eq!(newest.next, None);
+    assert_eq!(newest.prev, Some(Cursor::before(&newest.records[0])));
+    let context = recorder
+        .get_record_context(back.records[0].get_id(), 2, 1)
+        .unwrap()
+        .unwrap();
+    assert_eq!(context.record.sender_id, "2");
+    assert_eq!(
+        context
+            .before
+            .iter()
+            .chain(context.after.iter())
+            .map(|record| record.sender_id.as_str())
+            .collect::<Vec<_>>(),
+        vec!["0", "1", "3"]
+    );
+}
+
+#[test]
+fn test_query_sort() {
+    let mut recorder = test_recorder("sort");
+    for i in [2, 0, 1] {
+        assert!(recorder
+            .insert_or_update_record(
+                &Record {
+                    sender_id: i.to_string(),
+                    sender_name: i.to_string(),
+                    ..test_record("test_sort", "排序测试", i)
+                },
+                None
+            )
+            .unwrap());
+    }
+    recorder.refresh_index().unwrap();
+    let senders = |query: Query| {
+        recorder
+            .get_record(query)
+            .unwrap()
+            .iter()
+            .map(|record| record.sender_id.clone())
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    let query = Query {
+        chat_type: Some("test_sort".into()),
+        ..Default::default()
+    };
+    assert_eq!(senders(query.clone()), "0,1,2");
+    assert_eq!(
+        senders(Query {
+            sort: Some(QuerySort::TimestampDesc),
+            ..query.clone()
+        }),
+        "2,1,0"
+    );
+    assert_eq!(
+        senders(Query {
+            sort: Some(QuerySort::IdAsc),
+            ..query
+        }),
+        "2,0,1"
+    );
+    let query = Query {
+        keyword: Some("排序".into()),
+        ..Default::default()
+    };
+    assert_eq!(senders(query.clone()), "2,1,0");
+    assert_eq!(
+        senders(Query {
+            sort: Some(QuerySort::TimestampAsc),
+            ..query.clone()
+        }),
+        "0,1,2"
+    );
+    assert_eq!(
+        senders(Query {
+            sort: Some(QuerySort::IdDesc),
+            ..query
+        }),
+        "1,0,2"
+    );
+}
+
+#[test]
+fn test_string_filter() {
+    let mut recorder = test_recorder("filter");
+    for group in ["a_b", "axb", "a_bc"] {
+        assert!(recorder
+            .insert_or_update_record(
+                &Record {
+                    group_id: group.into(),
+                    ..test_record("test_filter", "hello", 1)
+                },
+                None
+            )
+            .unwrap());
+    }
+    let groups = |group_id: StringFilter| {
+        recorder
+            .get_record(Query {
+                chat_type: Some("test_filter".into()),
+                group_id: Some(group_id),
+                ..Default::default()
+            })
+            .unwrap()
+            .iter()
+            .map(|record| record.group_id.clone())
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    assert_eq!(groups("a_b".into()), "a_b");
+    assert_eq!(groups(StringFilter::Prefix("a_b".into())), "a_b,a_bc");
+    assert_eq!(groups(StringFilter::Like("a_b".into())), "a_b,axb");
+    assert_eq!(groups(StringFilter::Like("a\\_b%".into())), "a_b,a_bc");
+    assert_eq!(
+        groups(vec!["axb".to_string(), "a_bc".to_string()].into()),
+        "axb,a_bc"
+    );
+    assert_eq!(groups(StringFilter::any(["axb", "a_bc"]).negate()), "a_b");
+    assert_eq!(groups(StringFilter::Prefix("a_b".into()).negate()), "axb");
+    let axb = recorder
+        .get_record(Query {
+            chat_type: Some("test_filter".into()),
+            group_id: Some("axb".into()),
+            ..Default::default()
+        })
+        .unwrap()
+        .remove(0);
+    assert!(recorder
+        .insert_or_update_record(
+            (&axb, [("test".into(), vec![0])].iter().cloned().collect()),
+            None
+        )
+        .unwrap());
+    let filtered = |has_attachments, ids| {
+        recorder
+            .get_record(Query {
+                chat_type: Some("test_filter".into()),
+                has_attachments,
+                ids,
+                ..Default::default()
+            })
+            .unwrap()
+            .len()
+    };
+    assert_eq!(filtered(Some(true), None), 1);
+    assert_eq!(filtered(Some(false), None), 2);
+    assert_eq!(filtered(None, Some(vec![axb.get_id()])), 1);
+    assert_eq!(filtered(Some(false), Some(vec![axb.get_id()])), 0);
+    // more ids than sqlite binds as parameters
+    assert_eq!(filtered(None, Some((1..100_000).collect())), 3);
+}
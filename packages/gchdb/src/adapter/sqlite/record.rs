@@ -54,16 +54,23 @@ pub fn insert_or_update_record(
     recorder: &SqliteChatRecorder,
     record: &Record,
     attachs: &HashMap<String, Vec<u8>>,
-    metadata_merger: MetadataMerger<SqliteChatRecorder>,
+    metadata_merger: Option<&mut dyn MetadataMerger<SqliteChatRecorder>>,
 ) -> ChatRecordResult<bool> {
     Ok(
         if let RecordExistence::Exist(old_metadata) = check_record(conn, record)? {
             let mut record = record.clone();
             if let Some(metadata) = record.metadata {
-                record.metadata = if let Some(old_metadata) = old_metadata {
-                    metadata_merger(recorder, attachs, old_metadata, metadata)
-                } else {
-                    Some(metadata.clone())
+                record.metadata = match (old_metadata, metadata_merger) {
+                    (Some(old_metadata), Some(merger)) => {
+                        match merger.merge(recorder, attachs, old_metadata, metadata) {
+                            MergedMetadata::Merged(metadata) => Some(metadata),
+                            MergedMetadata::Removed => None,
+                            MergedMetadata::Conflict(reason) => {
+                                return Err(ChatRecordError::MetadataConflict(reason))
+                            }
+                        }
+                    }
+                    _ => Some(metadata),
                 }
             } else {
                 record.metadata = old_metadata
@@ -94,6 +101,35 @@ pub fn remove_record_by_id(conn: &mut SqliteConnection, id: i32) -> ChatRecordRe
         .execute(conn)?)
 }
 
+pub fn get_record_by_id(
+    conn: &mut SqliteConnection,
+    record_id: i32,
+) -> ChatRecordResult<Option<Record>> {
+    use schema::records::dsl::*;
+    Ok(records
+        .filter(id.eq(record_id))
+        .get_result(conn)
+        .optional()?)
+}
+
+/// load the stored record, the record is looked up by id or by its identity fields
+pub fn get_record_by_type(
+    conn: &mut SqliteConnection,
+    record: RecordType,
+) -> ChatRecordResult<Option<Record>> {
+    let record_id = match record {
+        RecordType::Id(record_id) => record_id,
+        record => match record.get_record() {
+            Some(record) => get_record_id(conn, record).or_else(|e| match e {
+                ChatRecordError::DieselError(DieselError::NotFound) => Ok(0),
+                e => Err(e),
+            })?,
+            None => 0,
+        },
+    };
+    get_record_by_id(conn, record_id)
+}
+
 pub fn get_record_id(conn: &mut SqliteConnection, record: &Record) -> ChatRecordResult<i32> {
     use schema::records::dsl::*;
     Ok(records
@@ -102,6 +138,7 @@ pub fn get_record_id(conn: &mut SqliteConnection, record: &Record) -> ChatRecord
                 .eq(&record.chat_type)
                 .and(owner_id.eq(&record.owner_id))
                 .and(group_id.eq(&record.group_id))
+                .and(sender_id.eq(&record.sender_id))
                 .and(timestamp.eq(record.timestamp))),
         )
         .select(id)
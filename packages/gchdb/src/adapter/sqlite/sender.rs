@@ -0,0 +1,291 @@
+use super::*;
+
+fn get_alias_before(
+    conn: &mut SqliteConnection,
+    alias: &SenderAlias,
+) -> ChatRecordResult<Option<SenderAlias>> {
+    use schema::sender_aliases::dsl::*;
+    Ok(sender_aliases
+        .filter(
+            chat_type
+                .eq(&alias.chat_type)
+                .and(sender_id.eq(&alias.sender_id))
+                .and(timestamp.le(alias.timestamp)),
+        )
+        .order(timestamp.desc())
+        .first(conn)
+        .optional()?)
+}
+
+fn get_alias_after(
+    conn: &mut SqliteConnection,
+    alias: &SenderAlias,
+) -> ChatRecordResult<Option<SenderAlias>> {
+    use schema::sender_aliases::dsl::*;
+    Ok(sender_aliases
+        .filter(
+            chat_type
+                .eq(&alias.chat_type)
+                .and(sender_id.eq(&alias.sender_id))
+                .and(timestamp.gt(alias.timestamp)),
+        )
+        .order(timestamp.asc())
+        .first(conn)
+        .optional()?)
+}
+
+fn update_alias_timestamp(
+    conn: &mut SqliteConnection,
+    alias_id: Option<i32>,
+    alias_timestamp: i64,
+) -> ChatRecordResult<usize> {
+    use schema::sender_aliases::dsl::*;
+    Ok(update(sender_aliases.filter(id.eq(alias_id)))
+        .set(timestamp.eq(alias_timestamp))
+        .execute(conn)?)
+}
+
+fn insert_alias(conn: &mut SqliteConnection, alias: &SenderAlias) -> ChatRecordResult<usize> {
+    Ok(insert_into(sender_aliases::table)
+        .values(alias)
+        .execute(conn)?)
+}
+
+fn check_sender(conn: &mut SqliteConnection, sender: &Sender) -> ChatRecordResult<bool> {
+    use schema::senders::dsl::*;
+    Ok(select(exists(
+        senders.filter(
+            chat_type
+                .eq(&sender.chat_type)
+                .and(sender_id.eq(&sender.sender_id)),
+        ),
+    ))
+    .get_result(conn)
+    .unwrap_or(false))
+}
+
+fn update_sender(conn: &mut SqliteConnection, sender: &Sender) -> ChatRecordResult<usize> {
+    use schema::senders::dsl::*;
+    Ok(update(
+        senders.filter(
+            chat_type
+                .eq(&sender.chat_type)
+                .and(sender_id.eq(&sender.sender_id)),
+        ),
+    )
+    .set((name.eq(&sender.name), timestamp.eq(sender.timestamp)))
+    .execute(conn)?)
+}
+
+fn insert_sender(conn: &mut SqliteConnection, sender: &Sender) -> ChatRecordResult<usize> {
+    Ok(insert_into(senders::table).values(sender).execute(conn)?)
+}
+
+fn refresh_sender(
+    conn: &mut SqliteConnection,
+    sender_chat_type: &str,
+    sender_sender_id: &str,
+) -> ChatRecordResult<bool> {
+    use schema::sender_aliases::dsl::*;
+    let latest = sender_aliases
+        .filter(
+            chat_type
+                .eq(sender_chat_type)
+                .and(sender_id.eq(sender_sender_id)),
+        )
+        .order(timestamp.desc())
+        .first::<SenderAlias>(conn)
+        .optional()?;
+    Ok(if let Some(latest) = latest {
+        let sender = Sender {
+            id: None,
+            chat_type: latest.chat_type,
+            sender_id: latest.sender_id,
+            name: latest.name,
+            timestamp: latest.timestamp,
+        };
+        if check_sender(conn, &sender)? {
+            update_sender(conn, &sender)
+        } else {
+            insert_sender(conn, &sender)
+        }? == 1
+    } else {
+        false
+    })
+}
+
+/// record that the sender used `alias.name` at `alias.timestamp`,
+/// only name changes and the last use of a name before a later change are stored,
+/// so the aliases of a sender stay a compact history even if records are imported out of order
+pub fn insert_or_update_sender_alias(
+    conn: &mut SqliteConnection,
+    alias: &SenderAlias,
+) -> ChatRecordResult<bool> {
+    let after = get_alias_after(conn, alias)?;
+    let changed = match get_alias_before(conn, alias)? {
+        Some(before) if before.name == alias.name => match after {
+            Some(after) if after.name != alias.name && before.timestamp < alias.timestamp => {
+                let first = get_alias_before(
+                    conn,
+                    &SenderAlias {
+                        timestamp: before.timestamp - 1,
+                        ..before.clone()
+                    },
+                )?;
+                match first {
+                    // move the last use of the name forward
+                    Some(first) if first.name == alias.name => {
+                        update_alias_timestamp(conn, before.id, alias.timestamp)?
+                    }
+                    _ => insert_alias(conn, alias)?,
+                }
+            }
+            // both neighbours already cover the name
+            _ => return Ok(true),
+        },
+        _ => match after {
+            Some(after) if after.name == alias.name => {
+                update_alias_timestamp(conn, after.id, alias.timestamp)?
+            }
+            _ => insert_alias(conn, alias)?,
+        },
+    } == 1;
+    Ok(changed && refresh_sender(conn, &alias.chat_type, &alias.sender_id)?)
+}
+
+pub fn get_sender(
+    conn: &mut SqliteConnection,
+    sender_chat_type: &str,
+    sender_sender_id: &str,
+) -> ChatRecordResult<Option<Sender>> {
+    use schema::senders::dsl::*;
+    Ok(senders
+        .filter(
+            chat_type
+                .eq(sender_chat_type)
+                .and(sender_id.eq(sender_sender_id)),
+        )
+        .get_result(conn)
+        .optional()?)
+}
+
+/// the first use of every contiguous run of a name, the stored last uses are skipped
+pub fn get_sender_aliases(
+    conn: &mut SqliteConnection,
+    sender_chat_type: &str,
+    sender_sender_id: &str,
+) -> ChatRecordResult<Vec<SenderAlias>> {
+    use schema::sender_aliases::dsl::*;
+    let mut aliases = sender_aliases
+        .filter(
+            chat_type
+                .eq(sender_chat_type)
+                .and(sender_id.eq(sender_sender_id)),
+        )
+        .order(timestamp.asc())
+        .load::<SenderAlias>(conn)?;
+    aliases.dedup_by(|alias, previous| alias.name == previous.name);
+    Ok(aliases)
+}
+
+pub fn resolve_sender_names(
+    conn: &mut SqliteConnection,
+    records: Vec<Record>,
+) -> ChatRecordResult<Vec<Record>> {
+    let mut names = HashMap::new();
+    records
+        .into_iter()
+        .map(|mut record| {
+            let key = (record.chat_type.clone(), record.sender_id.clone());
+            if !names.contains_key(&key) {
+                let sender = get_sender(conn, &key.0, &key.1)?;
+                names.insert(key.clone(), sender.map(|sender| sender.name));
+            }
+            if let Some(Some(name)) = names.get(&key) {
+                record.sender_name = name.clone();
+            }
+            Ok(record)
+        })
+        .collect()
+}
+
+#[test]
+fn test_sender_alias() {
+    let mut recorder = test_recorder("sender");
+    let mut record = Record {
+        sender_name: "旧名字".into(),
+        ..test_record("test_sender", "hello", 1)
+    };
+    assert!(recorder.insert_or_update_record(&record, None).unwrap());
+    record.sender_name = "新名字".into();
+    record.timestamp = 3;
+    assert!(recorder.insert_or_update_record(&record, None).unwrap());
+    record.sender_name = "旧名字".into();
+    record.timestamp = 2;
+    assert!(recorder.insert_or_update_record(&record, None).unwrap());
+    assert_eq!(
+        recorder
+            .get_sender_aliases("test_sender", "sender")
+            .unwrap()
+            .into_iter()
+            .map(|alias| (alias.name, alias.timestamp))
+            .collect::<Vec<_>>(),
+        vec![("旧名字".into(), 1), ("新名字".into(), 3)]
+    );
+    assert_eq!(
+        recorder
+            .get_sender("test_sender", "sender")
+            .unwrap()
+            .unwrap()
+            .name,
+        "新名字"
+    );
+    let records = recorder
+        .get_record(Query {
+            chat_type: Some("test_sender".into()),
+            resolve_sender_name: true,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(records.len(), 3);
+    assert!(records.iter().all(|record| record.sender_name == "新名字"));
+}
+
+#[test]
+fn test_sender_alias_out_of_order() {
+    let mut recorder = test_recorder("sender_order");
+    let mut observe = |name: &str, timestamp: i64| {
+        assert!(recorder
+            .insert_or_update_sender_alias(&SenderAlias {
+                id: None,
+                chat_type: "test_sender_order".into(),
+                sender_id: "sender".into(),
+                name: name.into(),
+                timestamp,
+            })
+            .unwrap());
+        recorder
+            .get_sender_aliases("test_sender_order", "sender")
+            .unwrap()
+            .into_iter()
+            .map(|alias| format!("{}@{}", alias.name, alias.timestamp))
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    assert_eq!(observe("old", 10), "old@10");
+    assert_eq!(observe("new", 30), "old@10,new@30");
+    assert_eq!(observe("old", 20), "old@10,new@30");
+    assert_eq!(observe("old", 25), "old@10,new@30");
+    assert_eq!(observe("old", 25), "old@10,new@30");
+    assert_eq!(observe("old", 15), "old@10,new@30");
+    assert_eq!(observe("new", 28), "old@10,new@28");
+    assert_eq!(observe("old", 40), "old@10,new@28,old@40");
+    assert_eq!(
+        recorder
+            .get_sender("test_sender_order", "sender")
+            .unwrap()
+            .unwrap()
+            .name,
+        "old"
+    );
+}
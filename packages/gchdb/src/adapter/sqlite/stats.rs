@@ -0,0 +1,246 @@
+use super::*;
+use diesel::{
+    dsl::{count_star, sql},
+    sql_types::{BigInt, Nullable, Text},
+};
+
+/// the filters of the query, limited to the keyword hits if some
+fn stats_filter<'a>(query: &'a Query, hits: Option<&[i32]>) -> RecordPredicate<'a> {
+    match hits {
+        Some(hits) => Box::new(query_filter(query).and(ids_filter(hits))),
+        None => query_filter(query),
+    }
+}
+
+/// count records matched by the query for each group,
+/// time groups are sorted by key, others by count in descending order
+pub fn count_records_by(
+    conn: &mut SqliteConnection,
+    query: &Query,
+    hits: Option<&[i32]>,
+    group: StatsGroup,
+) -> ChatRecordResult<Vec<StatsCount>> {
+    use schema::records::dsl::*;
+    let expression = group.get_expression();
+    let mut stats = records
+        .filter(stats_filter(query, hits))
+        .group_by(sql::<Text>(&expression))
+        .select((
+            sql::<Text>(&expression),
+            count_star(),
+            diesel::dsl::min(timestamp),
+            diesel::dsl::max(timestamp),
+        ))
+        .into_boxed();
+    stats = if group.is_time() {
+        stats.order(sql::<Text>(&expression).asc())
+    } else {
+        stats.order(count_star().desc())
+    };
+    if let Some(limit) = query.limit {
+        stats = stats.limit(limit.into());
+    }
+    Ok(stats
+        .load::<(String, i64, Option<i64>, Option<i64>)>(conn)?
+        .into_iter()
+        .map(|(key, count, first, last)| StatsCount {
+            key,
+            count,
+            first,
+            last,
+        })
+        .collect())
+}
+
+/// record counts indexed by day of week, `0` is sunday, and hour of day
+pub fn count_records_by_weekday_hour(
+    conn: &mut SqliteConnection,
+    query: &Query,
+    hits: Option<&[i32]>,
+    offset: i32,
+) -> ChatRecordResult<[[i64; 24]; 7]> {
+    let query = Query {
+        limit: None,
+        ..query.clone()
+    };
+    let mut counts = [[0; 24]; 7];
+    for stats in count_records_by(conn, &query, hits, StatsGroup::WeekdayHour(offset))? {
+        if let Some((weekday, hour)) = stats.key.split_once('-') {
+            if let (Ok(weekday), Ok(hour)) = (weekday.parse::<usize>(), hour.parse::<usize>()) {
+                counts[weekday][hour] = stats.count;
+            }
+        }
+    }
+    Ok(counts)
+}
+
+pub fn get_record_stats(
+    conn: &mut SqliteConnection,
+    query: &Query,
+    hits: Option<&[i32]>,
+) -> ChatRecordResult<RecordStats> {
+    use schema::records::dsl::*;
+    let (count, first, last, attachment_count, attachment_size) = records
+        .filter(stats_filter(query, hits))
+        .select((
+            count_star(),
+            diesel::dsl::min(timestamp),
+            diesel::dsl::max(timestamp),
+            sql::<Nullable<BigInt>>(
+                "SUM((SELECT COUNT(*) FROM attachments \
+                WHERE attachments.record_id = records.id))",
+            ),
+            sql::<Nullable<BigInt>>(
+                "SUM((SELECT SUM(LENGTH(blobs.blob)) FROM attachments \
+                INNER JOIN blobs ON blobs.hash = attachments.hash \
+                WHERE attachments.record_id = records.id))",
+            ),
+        ))
+        .get_result::<(i64, Option<i64>, Option<i64>, Option<i64>, Option<i64>)>(conn)?;
+    Ok(RecordStats {
+        count,
+        first,
+        last,
+        attachment_count: attachment_count.unwrap_or_default(),
+        attachment_size: attachment_size.unwrap_or_default(),
+    })
+}
+
+#[test]
+fn test_record_stats() {
+    let mut recorder = test_recorder("stats");
+    for (i, sender) in ["a", "a", "b"].iter().enumerate() {
+        assert!(recorder
+            .insert_or_update_record(
+                (
+                    &Record {
+                        sender_id: sender.to_string(),
+                        sender_name: sender.to_string(),
+                        ..test_record("test_stats", "hello", i as i64 * 3_600_000)
+                    },
+                    [("test".into(), vec![0, 1, 2, 3])]
+                        .iter()
+                        .cloned()
+                        .collect()
+                ),
+                None
+            )
+            .unwrap());
+    }
+    let query = Query {
+        chat_type: Some("test_stats".into()),
+        ..Default::default()
+    };
+    let senders = recorder
+        .count_records_by(&query, StatsGroup::Sender)
+        .unwrap();
+    assert_eq!(
+        senders
+            .iter()
+            .map(|stats| (stats.key.as_str(), stats.count))
+            .collect::<Vec<_>>(),
+        vec![("a", 2), ("b", 1)]
+    );
+    assert_eq!(senders[0].first, Some(0));
+    assert_eq!(senders[0].last, Some(3_600_000));
+    let hours = recorder
+        .count_records_by(&query, StatsGroup::Hour(8 * 3600))
+        .unwrap();
+    assert_eq!(
+        hours
+            .iter()
+            .map(|stats| stats.key.as_str())
+            .collect::<Vec<_>>(),
+        vec!["08", "09", "10"]
+    );
+    // 1970-01-01 is a thursday
+    let counts = recorder
+        .count_records_by_weekday_hour(&query, 8 * 3600)
+        .unwrap();
+    assert_eq!(&counts[4][8..11], &[1, 1, 1]);
+    assert_eq!(counts.iter().flatten().sum::<i64>(), 3);
+    let stats = recorder.get_record_stats(&query).unwrap();
+    assert_eq!(stats.count, 3);
+    assert_eq!(stats.first, Some(0));
+    assert_eq!(stats.last, Some(7_200_000));
+    assert_eq!(stats.attachment_count, 3);
+    assert_eq!(stats.attachment_size, 12);
+}
+
+#[test]
+fn test_count_records() {
+    let mut recorder = test_recorder("count");
+    for (i, (group, content)) in [
+        ("g1", "今天天气很好"),
+        ("g1", "明天天气不好"),
+        ("g2", "今天天气很好"),
+        ("g2", "晚安"),
+    ]
+    .iter()
+    .enumerate()
+    {
+        assert!(recorder
+            .insert_or_update_record(
+                &Record {
+                    group_id: group.to_string(),
+                    ..test_record("test_count", content, i as i64)
+                },
+                None
+            )
+            .unwrap());
+    }
+    recorder.refresh_index().unwrap();
+    let count = |group_id: Option<&str>, keyword: Option<&str>| {
+        let query = Query {
+            chat_type: Some("test_count".into()),
+            group_id: group_id.map(Into::into),
+            keyword: keyword.map(Into::into),
+            limit: Some(1),
+            ..Default::default()
+        };
+        (
+            recorder.count_records(&query).unwrap(),
+            recorder.exists(&query).unwrap(),
+        )
+    };
+    assert_eq!(count(None, None), (4, true));
+    assert_eq!(count(Some("g1"), None), (2, true));
+    assert_eq!(count(Some("g3"), None), (0, false));
+    assert_eq!(count(None, Some("天气")), (3, true));
+    assert_eq!(count(Some("g2"), Some("天气")), (1, true));
+    assert_eq!(count(Some("g2"), Some("明天")), (0, false));
+    let records = recorder
+        .get_record(Query {
+            group_id: Some("g2".into()),
+            keyword: Some("天气".into()),
+            limit: Some(1),
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].timestamp, 2);
+    assert_eq!(
+        recorder
+            .count_records(&Query {
+                keyword: Some("天气".into()),
+                ..Default::default()
+            })
+            .unwrap(),
+        3
+    );
+    let query = Query {
+        chat_type: Some("test_count".into()),
+        keyword: Some("天气".into()),
+        ..Default::default()
+    };
+    assert_eq!(
+        recorder
+            .count_records_by(&query, StatsGroup::Group)
+            .unwrap()
+            .iter()
+            .map(|stats| (stats.key.as_str(), stats.count))
+            .collect::<Vec<_>>(),
+        vec![("g1", 2), ("g2", 1)]
+    );
+    assert_eq!(recorder.get_record_stats(&query).unwrap().count, 3);
+}
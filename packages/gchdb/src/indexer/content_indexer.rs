@@ -1,8 +1,23 @@
 use super::*;
+use chrono::Datelike;
 use std::time::Instant;
+use tantivy::tokenizer::TokenStream;
 use tantivy::{
-    collector::TopDocs, query::QueryParser, schema::Schema, Index, IndexReader, IndexWriter, Order,
-    ReloadPolicy, TantivyDocument,
+    aggregation::{
+        agg_req::{Aggregation, AggregationVariants},
+        agg_result::{AggregationResult, BucketEntries, BucketResult},
+        bucket::{HistogramAggregation, TermsAggregation},
+        AggregationCollector, Key,
+    },
+    collector::{Count, DocSetCollector, FacetCollector, TopDocs},
+    query::{
+        BooleanQuery, ConstScoreQuery, EmptyQuery, ExistsQuery, FuzzyTermQuery, MoreLikeThisQuery,
+        Occur, PhrasePrefixQuery, PhraseQuery, Query as TantivyQuery, QueryParser, RegexQuery,
+        TermQuery, TermSetQuery,
+    },
+    schema::{Facet, Field, IndexRecordOption, OwnedValue, Schema},
+    DocAddress, Index, IndexReader, IndexWriter, Order, ReloadPolicy, Searcher, TantivyDocument,
+    Term,
 };
 
 pub struct ContentIndexer {
@@ -14,7 +29,16 @@ pub struct ContentIndexer {
 
 impl ContentIndexer {
     pub fn new() -> ChatRecordResult<Self> {
-        let fields = Fields::default();
+        Self::with_fields(Fields::default())
+    }
+
+    /// index the values at the dot separated keys of the metadata, so keyword search matches them,
+    /// the query parser finds the value of `reply.id` in the field `meta_reply_id`
+    pub fn with_metadata(codec: MetadataCodec, keys: &[String]) -> ChatRecordResult<Self> {
+        Self::with_fields(Fields::with_metadata(codec, keys))
+    }
+
+    fn with_fields(fields: Fields) -> ChatRecordResult<Self> {
         let (index, reader) = Self::get_index_handle(fields.schema.clone())?;
         let writer = Self::get_index_writer(&index)?;
 
@@ -48,13 +72,18 @@ impl ContentIndexer {
         Ok(())
     }
 
-    pub fn gen_index<D: GetDocument>(&mut self, records: Vec<D>) -> ChatRecordResult<()> {
-        let total = records.len() as f64;
+    /// `total` is only used to report progress
+    pub fn gen_index<D, I>(&mut self, records: I, total: usize) -> ChatRecordResult<()>
+    where
+        D: GetDocument,
+        I: IntoIterator<Item = ChatRecordResult<D>>,
+    {
+        let total = total as f64;
         let mut last_parent = 0.0;
         let mut sw = Instant::now();
-        for (i, metadata) in records.iter().enumerate() {
+        for (i, metadata) in records.into_iter().enumerate() {
             self.writer
-                .add_document(metadata.get_document(&self.fields)?)?;
+                .add_document(metadata?.get_document(&self.fields)?)?;
             if total > 200.0 && i as f64 / total - last_parent >= 0.01 {
                 last_parent = i as f64 / total;
                 debug!(
@@ -73,39 +102,581 @@ impl ContentIndexer {
         Ok(())
     }
 
-    pub fn search(&self, offset: i64, limit: i64, query: &str) -> ChatRecordResult<Vec<i32>> {
+    pub fn search(
+        &self,
+        offset: i64,
+        limit: i64,
+        query: &SearchRequest,
+    ) -> ChatRecordResult<Vec<i32>> {
+        self.search_with_sort(offset, limit, query, None, QuerySort::TimestampDesc)
+    }
+
+    /// search record ids in the given order, `QuerySort::Relevance` orders by score,
+    /// `ids` further limits the records like in `date_histogram`
+    pub fn search_with_sort(
+        &self,
+        offset: i64,
+        limit: i64,
+        query: &SearchRequest,
+        ids: Option<&[i32]>,
+        sort: QuerySort,
+    ) -> ChatRecordResult<Vec<i32>> {
+        let offset = if offset > 0 { offset as usize } else { 0 };
+        if limit <= 0 {
+            return Ok(vec![]);
+        }
+        let searcher = self.reader.searcher();
+        let query = self.restrict_ids(self.build_query(query)?, ids);
+        let collector = || TopDocs::with_limit(limit as usize).and_offset(offset);
+        let order_by = |field: &str, order: Order| {
+            searcher
+                .search(
+                    &query,
+                    &collector().order_by_fast_field::<i64>(field, order),
+                )
+                .map(|docs| docs.into_iter().map(|(_, doc)| doc).collect::<Vec<_>>())
+        };
+        let docs = match sort {
+            QuerySort::TimestampAsc => order_by("timestamp", Order::Asc)?,
+            QuerySort::TimestampDesc => order_by("timestamp", Order::Desc)?,
+            QuerySort::IdAsc => order_by("idx", Order::Asc)?,
+            QuerySort::IdDesc => order_by("idx", Order::Desc)?,
+            QuerySort::Relevance => searcher
+                .search(&query, &collector())?
+                .into_iter()
+                .map(|(_, doc)| doc)
+                .collect(),
+        };
+        Ok(docs
+            .into_iter()
+            .filter_map(|doc_address| self.get_record_id(&searcher, doc_address))
+            .collect())
+    }
+
+    pub fn num_docs(&self) -> u64 {
+        self.reader.searcher().num_docs()
+    }
+
+    pub fn count(&self, query: &SearchRequest) -> ChatRecordResult<usize> {
+        Ok(self
+            .reader
+            .searcher()
+            .search(&self.build_query(query)?, &Count)?)
+    }
+
+    /// top hits of the query together with the counts of the facets in all hits
+    pub fn search_with_facets(
+        &self,
+        offset: i64,
+        limit: i64,
+        query: &SearchRequest,
+        sort: QuerySort,
+        facets: &[FacetField],
+    ) -> ChatRecordResult<(Vec<i32>, FacetCounts)> {
+        Ok((
+            self.search_with_sort(offset, limit, query, None, sort)?,
+            self.facet_counts(Some(query), None, facets)?,
+        ))
+    }
+
+    /// counts of the facets in records matched by the query, or in all records,
+    /// most frequent first, `ids` further limits the records like in `date_histogram`
+    pub fn facet_counts(
+        &self,
+        query: Option<&SearchRequest>,
+        ids: Option<&[i32]>,
+        facets: &[FacetField],
+    ) -> ChatRecordResult<FacetCounts> {
+        let searcher = self.reader.searcher();
+        let query = self.restrict_ids(self.build_query_or_all(query)?, ids);
+        facets
+            .iter()
+            .map(|facet| Ok((facet.clone(), self.count_facet(&searcher, &*query, facet)?)))
+            .collect()
+    }
+
+    fn count_facet(
+        &self,
+        searcher: &Searcher,
+        query: &dyn TantivyQuery,
+        facet: &FacetField,
+    ) -> ChatRecordResult<Vec<FacetCount>> {
+        let chat_types = |chat_type: &Option<String>| -> ChatRecordResult<Vec<Facet>> {
+            Ok(match chat_type {
+                Some(chat_type) => vec![Facet::from_path(vec![chat_type])],
+                None => self
+                    .collect_facets(searcher, query, self.fields.chat, &[Facet::root()])?
+                    .into_iter()
+                    .map(|count| Facet::from_path(count.path))
+                    .collect(),
+            })
+        };
+        match facet {
+            FacetField::ChatType => {
+                self.collect_facets(searcher, query, self.fields.chat, &[Facet::root()])
+            }
+            FacetField::Group(chat_type) => {
+                self.collect_facets(searcher, query, self.fields.chat, &chat_types(chat_type)?)
+            }
+            FacetField::Sender(chat_type) => {
+                self.collect_facets(searcher, query, self.fields.sender, &chat_types(chat_type)?)
+            }
+            FacetField::Month => {
+                self.collect_facets(searcher, query, self.fields.month, &[Facet::root()])
+            }
+        }
+    }
+
+    /// counts of the children of the prefixes
+    fn collect_facets(
+        &self,
+        searcher: &Searcher,
+        query: &dyn TantivyQuery,
+        field: Field,
+        prefixes: &[Facet],
+    ) -> ChatRecordResult<Vec<FacetCount>> {
+        if prefixes.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut collector = FacetCollector::for_field(self.fields.schema.get_field_name(field));
+        for prefix in prefixes {
+            collector.add_facet(prefix.clone());
+        }
+        let facet_counts = searcher.search(query, &collector)?;
+        let mut counts = prefixes
+            .iter()
+            .flat_map(|prefix| {
+                facet_counts
+                    .get(prefix.clone())
+                    .map(|(facet, count)| FacetCount {
+                        path: facet.to_path().into_iter().map(Into::into).collect(),
+                        count,
+                    })
+            })
+            .collect::<Vec<_>>();
+        counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.path.cmp(&b.path)));
+        Ok(counts)
+    }
+
+    fn restrict_ids(
+        &self,
+        query: Box<dyn TantivyQuery>,
+        ids: Option<&[i32]>,
+    ) -> Box<dyn TantivyQuery> {
+        match ids {
+            Some(ids) => Box::new(BooleanQuery::intersection(vec![
+                query,
+                Box::new(TermSetQuery::new(
+                    ids.iter()
+                        .map(|id| Term::from_field_i64(self.fields.idx, *id as i64)),
+                )),
+            ])),
+            None => query,
+        }
+    }
+
+    /// count records matched by the query, or all records, over time,
+    /// `ids` further limits the records, e.g. to the results of filters applied in database
+    pub fn date_histogram(
+        &self,
+        query: Option<&SearchRequest>,
+        ids: Option<&[i32]>,
+        interval: HistogramInterval,
+    ) -> ChatRecordResult<Vec<HistogramBucket>> {
+        let query = self.restrict_ids(self.build_query_or_all(query)?, ids);
+        let offset = interval.get_offset() as i64 * 1000;
+        let (size, start) = match interval {
+            // 1970-01-05 is a monday
+            HistogramInterval::Week(_) => (HistogramInterval::DAY * 7, HistogramInterval::DAY * 4),
+            HistogramInterval::Day(_) | HistogramInterval::Month(_) => (HistogramInterval::DAY, 0),
+        };
+        let aggregations = vec![(
+            "histogram".to_string(),
+            Aggregation {
+                agg: AggregationVariants::Histogram(HistogramAggregation {
+                    field: "timestamp".into(),
+                    interval: size as f64,
+                    offset: Some((start - offset).rem_euclid(size) as f64),
+                    ..Default::default()
+                }),
+                sub_aggregation: Default::default(),
+            },
+        )]
+        .into_iter()
+        .collect();
+        let collector = AggregationCollector::from_aggs(aggregations, Default::default());
+        let mut result = self.reader.searcher().search(&query, &collector)?;
+        let buckets = match result.0.remove("histogram") {
+            Some(AggregationResult::BucketResult(BucketResult::Histogram {
+                buckets: BucketEntries::Vec(buckets),
+            })) => buckets,
+            _ => vec![],
+        };
+        let local_date = |start: i64| {
+            chrono::DateTime::from_timestamp_millis(start + offset)
+                .unwrap_or_default()
+                .date_naive()
+        };
+        let mut histogram: Vec<HistogramBucket> = vec![];
+        for bucket in buckets {
+            let start = match bucket.key {
+                Key::F64(start) => start as i64,
+                Key::Str(_) => continue,
+            };
+            let date = local_date(start);
+            match interval {
+                HistogramInterval::Month(_) => {
+                    let key = date.format("%Y-%m").to_string();
+                    match histogram.last_mut() {
+                        Some(last) if last.key == key => last.count += bucket.doc_count,
+                        _ => histogram.push(HistogramBucket {
+                            key,
+                            start: date
+                                .with_day(1)
+                                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                                .map(|time| time.and_utc().timestamp_millis() - offset)
+                                .unwrap_or(start),
+                            count: bucket.doc_count,
+                        }),
+                    }
+                }
+                _ => histogram.push(HistogramBucket {
+                    key: date.format("%Y-%m-%d").to_string(),
+                    start,
+                    count: bucket.doc_count,
+                }),
+            }
+        }
+        Ok(histogram)
+    }
+
+    /// most frequent values of the entity in records matched by the query, or in all records,
+    /// `ids` further limits the records like in `date_histogram`
+    pub fn top_entities(
+        &self,
+        kind: EntityKind,
+        query: Option<&SearchRequest>,
+        ids: Option<&[i32]>,
+        limit: u32,
+    ) -> ChatRecordResult<Vec<EntityCount>> {
+        let query = self.restrict_ids(self.build_query_or_all(query)?, ids);
+        let aggregations = vec![(
+            "top".to_string(),
+            Aggregation {
+                agg: AggregationVariants::Terms(TermsAggregation {
+                    field: kind.field_name().into(),
+                    size: Some(limit),
+                    ..Default::default()
+                }),
+                sub_aggregation: Default::default(),
+            },
+        )]
+        .into_iter()
+        .collect();
+        let collector = AggregationCollector::from_aggs(aggregations, Default::default());
+        let mut result = self.reader.searcher().search(&query, &collector)?;
+        Ok(match result.0.remove("top") {
+            Some(AggregationResult::BucketResult(BucketResult::Terms { buckets, .. })) => buckets
+                .into_iter()
+                .filter_map(|bucket| match bucket.key {
+                    Key::Str(value) => Some(EntityCount {
+                        value,
+                        count: bucket.doc_count,
+                    }),
+                    Key::F64(_) => None,
+                })
+                .collect(),
+            _ => vec![],
+        })
+    }
+
+    /// ids of all records matched by the query, in no particular order
+    pub fn search_all(&self, query: &SearchRequest) -> ChatRecordResult<Vec<i32>> {
+        let searcher = self.reader.searcher();
+        Ok(searcher
+            .search(&self.build_query(query)?, &DocSetCollector)?
+            .into_iter()
+            .filter_map(|doc_address| self.get_record_id(&searcher, doc_address))
+            .collect())
+    }
+
+    /// ids of records with content similar to the text in descending order of relevance,
+    /// limited to records matched by the query and in `ids` if they are some, `exclude` is skipped
+    #[allow(clippy::too_many_arguments)]
+    pub fn more_like_this(
+        &self,
+        text: &str,
+        options: &MoreLikeThisOptions,
+        query: Option<&SearchRequest>,
+        ids: Option<&[i32]>,
+        exclude: Option<i32>,
+        offset: i64,
+        limit: i64,
+    ) -> ChatRecordResult<Vec<i32>> {
+        if limit <= 0 {
+            return Ok(vec![]);
+        }
+        let mut builder = MoreLikeThisQuery::builder()
+            .with_min_doc_frequency(options.min_doc_frequency)
+            .with_min_term_frequency(options.min_term_frequency)
+            .with_max_query_terms(options.max_query_terms)
+            .with_stop_words(options.stop_words.clone());
+        if let Some(max_doc_frequency) = options.max_doc_frequency {
+            builder = builder.with_max_doc_frequency(max_doc_frequency);
+        }
+        if let Some(min_word_length) = options.min_word_length {
+            builder = builder.with_min_word_length(min_word_length);
+        }
+        let mut clauses: Vec<(Occur, Box<dyn TantivyQuery>)> = vec![(
+            Occur::Must,
+            Box::new(builder.with_document_fields(vec![(
+                self.fields.content,
+                vec![OwnedValue::Str(text.into())],
+            )])),
+        )];
+        // filters do not change the relevance
+        if let Some(query) = query {
+            clauses.push((
+                Occur::Must,
+                Box::new(ConstScoreQuery::new(self.build_query(query)?, 0.0)),
+            ));
+        }
+        if let Some(ids) = ids {
+            clauses.push((
+                Occur::Must,
+                Box::new(ConstScoreQuery::new(
+                    Box::new(TermSetQuery::new(
+                        ids.iter()
+                            .map(|id| Term::from_field_i64(self.fields.idx, *id as i64)),
+                    )),
+                    0.0,
+                )),
+            ));
+        }
+        if let Some(exclude) = exclude {
+            clauses.push((
+                Occur::MustNot,
+                Box::new(TermQuery::new(
+                    Term::from_field_i64(self.fields.idx, exclude as i64),
+                    IndexRecordOption::Basic,
+                )),
+            ));
+        }
+        let searcher = self.reader.searcher();
         let offset = if offset > 0 { offset as usize } else { 0 };
+        Ok(searcher
+            .search(
+                &BooleanQuery::new(clauses),
+                &TopDocs::with_limit(limit as usize).and_offset(offset),
+            )?
+            .into_iter()
+            .filter_map(|(_, doc_address)| self.get_record_id(&searcher, doc_address))
+            .collect())
+    }
+
+    /// `(record id, attachment id)` of attachments whose extracted text is matched by the query,
+    /// in descending order of relevance, limited to attachments of records in `ids` if it is some
+    pub fn search_attachments(
+        &self,
+        offset: i64,
+        limit: i64,
+        query: &SearchRequest,
+        ids: Option<&[i32]>,
+    ) -> ChatRecordResult<Vec<(i32, i32)>> {
+        if limit <= 0 {
+            return Ok(vec![]);
+        }
+        let mut query = self.build_fields_query(query, &[self.fields.attachment])?;
+        if let Some(ids) = ids {
+            query = Box::new(BooleanQuery::new(vec![
+                (Occur::Must, query),
+                (
+                    Occur::Must,
+                    Box::new(ConstScoreQuery::new(
+                        Box::new(TermSetQuery::new(
+                            ids.iter()
+                                .map(|id| Term::from_field_i64(self.fields.idx, *id as i64)),
+                        )),
+                        0.0,
+                    )),
+                ),
+            ]));
+        }
         let searcher = self.reader.searcher();
+        let offset = if offset > 0 { offset as usize } else { 0 };
         Ok(searcher
             .search(
-                &QueryParser::for_index(
-                    &self.index,
-                    self.fields
-                        .custom
-                        .iter()
-                        .cloned()
-                        .chain(vec![self.fields.content])
-                        .collect(),
-                )
-                .parse_query(query)?,
-                &TopDocs::with_limit(offset + limit as usize)
-                    .order_by_u64_field("timestamp", Order::Desc),
+                &query,
+                &TopDocs::with_limit(limit as usize).and_offset(offset),
             )?
+            .into_iter()
+            .filter_map(|(_, doc_address)| {
+                Some((
+                    self.get_record_id(&searcher, doc_address)?,
+                    self.get_i64(&searcher, doc_address, self.fields.attachment_id)? as i32,
+                ))
+            })
+            .collect())
+    }
+
+    /// content and the optional pinyin field, searched together
+    fn search_fields(&self) -> Vec<Field> {
+        self.fields
+            .custom
             .iter()
-            .skip(offset)
-            .filter_map(|(_score, doc_address)| {
-                use tantivy::schema::OwnedValue;
-                searcher
-                    .doc::<TantivyDocument>(*doc_address)
-                    .ok()
-                    .and_then(|doc| {
-                        doc.get_first(self.fields.idx).and_then(|val| match val {
-                            OwnedValue::U64(val) => Some(*val as i32),
-                            OwnedValue::I64(val) => Some(*val as i32),
-                            _ => None,
-                        })
+            .cloned()
+            .chain(Some(self.fields.content))
+            .chain(self.fields.pinyin)
+            .collect()
+    }
+
+    fn build_query_or_all(
+        &self,
+        query: Option<&SearchRequest>,
+    ) -> ChatRecordResult<Box<dyn TantivyQuery>> {
+        Ok(match query {
+            Some(query) => self.build_query(query)?,
+            // attachment documents have no timestamp
+            None => Box::new(ExistsQuery::new_exists_query("timestamp".into())),
+        })
+    }
+
+    fn query_parser(&self, fields: &[Field]) -> QueryParser {
+        QueryParser::for_index(&self.index, fields.to_vec())
+    }
+
+    /// tokenize the text with the analyzer of the content field, keeping token positions,
+    /// so pinyin is not expanded at query time
+    fn tokenize(&self, field: Field, text: &str) -> ChatRecordResult<Vec<(usize, Term)>> {
+        let mut analyzer = self.index.tokenizer_for_field(self.fields.content)?;
+        let mut stream = analyzer.token_stream(text);
+        let mut terms = vec![];
+        stream.process(&mut |token| {
+            terms.push((token.position, Term::from_field_text(field, &token.text)))
+        });
+        Ok(terms)
+    }
+
+    fn build_query(&self, request: &SearchRequest) -> ChatRecordResult<Box<dyn TantivyQuery>> {
+        self.build_fields_query(request, &self.search_fields())
+    }
+
+    /// the request searched in the text fields
+    fn build_fields_query(
+        &self,
+        request: &SearchRequest,
+        fields: &[Field],
+    ) -> ChatRecordResult<Box<dyn TantivyQuery>> {
+        Ok(match request {
+            SearchRequest::Query(query) => self.query_parser(fields).parse_query(query)?,
+            SearchRequest::Lenient(query) => {
+                let parser = self.query_parser(fields);
+                match parser.parse_query(query) {
+                    Ok(query) => query,
+                    Err(_) => parser.parse_query(&SearchRequest::escape(query))?,
+                }
+            }
+            SearchRequest::Entity(kind, value) => Box::new(TermQuery::new(
+                Term::from_field_text(self.fields.entity(*kind), &kind.normalize(value)),
+                IndexRecordOption::Basic,
+            )),
+            SearchRequest::All(requests) => Box::new(BooleanQuery::intersection(
+                requests
+                    .iter()
+                    .map(|request| self.build_fields_query(request, fields))
+                    .collect::<ChatRecordResult<Vec<_>>>()?,
+            )),
+            request => {
+                let mut queries = fields
+                    .iter()
+                    .map(|field| self.build_field_query(*field, request))
+                    .collect::<ChatRecordResult<Vec<_>>>()?;
+                if queries.len() == 1 {
+                    queries.remove(0)
+                } else {
+                    Box::new(BooleanQuery::union(queries))
+                }
+            }
+        })
+    }
+
+    fn build_field_query(
+        &self,
+        field: Field,
+        request: &SearchRequest,
+    ) -> ChatRecordResult<Box<dyn TantivyQuery>> {
+        let term_query = |term: Term| -> Box<dyn TantivyQuery> {
+            Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs))
+        };
+        let combine =
+            |occur: Occur, queries: Vec<Box<dyn TantivyQuery>>| -> Box<dyn TantivyQuery> {
+                if queries.is_empty() {
+                    Box::new(EmptyQuery)
+                } else {
+                    Box::new(BooleanQuery::new(
+                        queries.into_iter().map(|query| (occur, query)).collect(),
+                    ))
+                }
+            };
+        Ok(match request {
+            SearchRequest::Query(_)
+            | SearchRequest::Lenient(_)
+            | SearchRequest::Entity(..)
+            | SearchRequest::All(_) => self.build_fields_query(request, &[field])?,
+            SearchRequest::Terms { text, operator } => combine(
+                match operator {
+                    SearchOperator::And => Occur::Must,
+                    SearchOperator::Or => Occur::Should,
+                },
+                self.tokenize(field, text)?
+                    .into_iter()
+                    .map(|(_, term)| term_query(term))
+                    .collect(),
+            ),
+            SearchRequest::Phrase(text) => {
+                let mut terms = self.tokenize(field, text)?;
+                match terms.len() {
+                    0 => Box::new(EmptyQuery),
+                    1 => term_query(terms.remove(0).1),
+                    _ => Box::new(PhraseQuery::new_with_offset(terms)),
+                }
+            }
+            SearchRequest::Prefix(text) => {
+                let terms = self.tokenize(field, text)?;
+                if terms.is_empty() {
+                    Box::new(EmptyQuery)
+                } else {
+                    Box::new(PhrasePrefixQuery::new_with_offset(terms))
+                }
+            }
+            SearchRequest::Fuzzy { text, distance } => combine(
+                Occur::Must,
+                self.tokenize(field, text)?
+                    .into_iter()
+                    .map(|(_, term)| -> Box<dyn TantivyQuery> {
+                        Box::new(FuzzyTermQuery::new(term, *distance, true))
                     })
+                    .collect(),
+            ),
+            SearchRequest::Regex(pattern) => Box::new(RegexQuery::from_pattern(pattern, field)?),
+        })
+    }
+
+    fn get_record_id(&self, searcher: &Searcher, doc_address: DocAddress) -> Option<i32> {
+        self.get_i64(searcher, doc_address, self.fields.idx)
+            .map(|idx| idx as i32)
+    }
+
+    fn get_i64(&self, searcher: &Searcher, doc_address: DocAddress, field: Field) -> Option<i64> {
+        searcher
+            .doc::<TantivyDocument>(doc_address)
+            .ok()
+            .and_then(|doc| {
+                doc.get_first(field).and_then(|val| match val {
+                    OwnedValue::U64(val) => Some(*val as i64),
+                    OwnedValue::I64(val) => Some(*val),
+                    _ => None,
+                })
             })
-            .collect())
     }
 }
@@ -0,0 +1,187 @@
+/// extracts searchable text from the data of an attachment
+pub trait TextExtractor: Send + Sync {
+    /// returns none if the data can not be extracted
+    fn extract(&self, data: &[u8]) -> Option<String>;
+}
+
+impl<F> TextExtractor for F
+where
+    F: Fn(&[u8]) -> Option<String> + Send + Sync,
+{
+    fn extract(&self, data: &[u8]) -> Option<String> {
+        self(data)
+    }
+}
+
+/// utf-8 text, invalid sequences are replaced
+pub struct PlainTextExtractor;
+
+impl TextExtractor for PlainTextExtractor {
+    fn extract(&self, data: &[u8]) -> Option<String> {
+        let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+        Some(String::from_utf8_lossy(data).into_owned())
+    }
+}
+
+/// text of html without tags, scripts and styles, common entities are decoded
+pub struct HtmlExtractor;
+
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => match entity.strip_prefix('#') {
+            Some(code) => match code.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => code.parse().ok(),
+            }
+            .and_then(char::from_u32),
+            None => None,
+        },
+    }
+}
+
+impl TextExtractor for HtmlExtractor {
+    fn extract(&self, data: &[u8]) -> Option<String> {
+        let html = String::from_utf8_lossy(data);
+        let lower = html.to_ascii_lowercase();
+        let mut text = String::with_capacity(html.len());
+        let mut i = 0;
+        while i < html.len() {
+            let rest = &html[i..];
+            if rest.starts_with("<!--") {
+                i += rest.find("-->").map(|end| end + 3).unwrap_or(rest.len());
+            } else if rest.starts_with('<') {
+                let end = rest.find('>').map(|end| end + 1).unwrap_or(rest.len());
+                let tag = &lower[i + 1..i + end];
+                i += end;
+                for skipped in ["script", "style"] {
+                    if tag.starts_with(skipped) {
+                        let close = format!("</{}", skipped);
+                        i = lower[i..]
+                            .find(&close)
+                            .map(|start| i + start)
+                            .unwrap_or(html.len());
+                    }
+                }
+                // tags separate words
+                if !text.ends_with(char::is_whitespace) {
+                    text.push(' ');
+                }
+            } else if rest.starts_with('&') {
+                match rest.as_bytes()[1..]
+                    .iter()
+                    .take(10)
+                    .position(|b| *b == b';')
+                    .and_then(|end| Some((end, decode_entity(&rest[1..end + 1])?)))
+                {
+                    Some((end, c)) => {
+                        text.push(c);
+                        i += end + 2;
+                    }
+                    None => {
+                        text.push('&');
+                        i += 1;
+                    }
+                }
+            } else {
+                let c = rest.chars().next().unwrap_or_default();
+                text.push(c);
+                i += c.len_utf8();
+            }
+        }
+        Some(text.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+}
+
+/// text of pdf documents, malformed documents are skipped,
+/// pdf-extract may panic on them, which is only caught if panics unwind
+#[cfg(feature = "pdf")]
+pub struct PdfExtractor;
+
+#[cfg(feature = "pdf")]
+impl TextExtractor for PdfExtractor {
+    fn extract(&self, data: &[u8]) -> Option<String> {
+        std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem(data))
+            .ok()?
+            .ok()
+    }
+}
+
+/// mime type of the attachment by the extension of its name
+pub fn guess_mime(name: &str) -> Option<&'static str> {
+    let extension = name.rsplit_once('.')?.1.to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "txt" | "text" | "log" | "md" | "csv" | "tsv" => "text/plain",
+        "htm" | "html" | "xhtml" => "text/html",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        _ => return None,
+    })
+}
+
+/// extractors keyed by the extension of the attachment name, e.g. `.txt`,
+/// or by a mime type, e.g. `text/plain` or `text/*`, which is guessed from the name,
+/// the first matched extractor is used
+pub struct TextExtractors {
+    extractors: Vec<(String, Box<dyn TextExtractor>)>,
+}
+
+impl Default for TextExtractors {
+    /// plain text and html, and pdf if `pdf` is enabled
+    fn default() -> Self {
+        let extractors = Self::empty()
+            .register("text/plain", PlainTextExtractor)
+            .register("text/html", HtmlExtractor);
+        #[cfg(feature = "pdf")]
+        let extractors = extractors.register("application/pdf", PdfExtractor);
+        extractors
+    }
+}
+
+impl TextExtractors {
+    /// no attachment is extracted
+    pub fn empty() -> Self {
+        Self { extractors: vec![] }
+    }
+
+    /// extractors registered later take precedence
+    pub fn register<S, E>(mut self, key: S, extractor: E) -> Self
+    where
+        S: Into<String>,
+        E: TextExtractor + 'static,
+    {
+        self.extractors
+            .insert(0, (key.into().to_ascii_lowercase(), Box::new(extractor)));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn TextExtractor> {
+        let name = name.to_ascii_lowercase();
+        let mime = guess_mime(&name);
+        self.extractors
+            .iter()
+            .find(|(key, _)| {
+                if key.starts_with('.') {
+                    name.ends_with(key.as_str())
+                } else if let Some(prefix) = key.strip_suffix("/*") {
+                    mime.and_then(|mime| mime.split_once('/'))
+                        .map(|(kind, _)| kind == prefix)
+                        .unwrap_or(false)
+                } else {
+                    mime == Some(key.as_str())
+                }
+            })
+            .map(|(_, extractor)| extractor.as_ref())
+    }
+
+    /// text of the attachment, none if no extractor matches its name
+    pub fn extract(&self, name: &str, data: &[u8]) -> Option<String> {
+        self.get(name)?.extract(data)
+    }
+}
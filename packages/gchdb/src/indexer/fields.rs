@@ -4,9 +4,24 @@ use tantivy::schema::*;
 pub struct Fields {
     pub idx: Field,
     pub content: Field,
+    /// pinyin and initials of the content, only indexed if `pinyin` is enabled
+    pub pinyin: Option<Field>,
     pub timestamp: Field,
-    #[allow(dead_code)]
+    /// raw values of the entities extracted from the content
+    pub entities: Vec<(EntityKind, Field)>,
+    /// `/chat_type/group_id`
+    pub chat: Field,
+    /// `/chat_type/sender_id`
+    pub sender: Field,
+    /// `/yyyy-mm`
+    pub month: Field,
+    /// text extracted from an attachment, indexed in a separate document with the record id in `idx`
+    pub attachment: Field,
+    pub attachment_id: Field,
     pub custom: Vec<Field>,
+    /// metadata keys indexed in the custom fields
+    pub metadata: Vec<(String, Field)>,
+    pub metadata_codec: MetadataCodec,
     pub schema: Schema,
 }
 
@@ -18,7 +33,7 @@ impl Fields {
             custom_field.push(schema_builder.add_text_field(name.to_string().as_str(), options));
         }
         Self {
-            idx: schema_builder.add_i64_field("idx", FAST | STORED),
+            idx: schema_builder.add_i64_field("idx", FAST | STORED | INDEXED),
             content: schema_builder.add_text_field(
                 "content",
                 TextOptions::default().set_indexing_options(
@@ -27,13 +42,104 @@ impl Fields {
                         .set_index_option(IndexRecordOption::WithFreqsAndPositions),
                 ),
             ),
+            pinyin: if cfg!(feature = "pinyin") {
+                Some(
+                    schema_builder.add_text_field(
+                        "content_pinyin",
+                        TextOptions::default().set_indexing_options(
+                            TextFieldIndexing::default()
+                                .set_tokenizer(LANG_CN_PINYIN)
+                                .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+                        ),
+                    ),
+                )
+            } else {
+                None
+            },
             timestamp: schema_builder.add_i64_field("timestamp", FAST),
+            entities: EntityKind::all()
+                .iter()
+                .map(|kind| {
+                    (
+                        *kind,
+                        schema_builder.add_text_field(kind.field_name(), STRING | FAST),
+                    )
+                })
+                .collect(),
+            chat: schema_builder.add_facet_field("chat", FacetOptions::default()),
+            sender: schema_builder.add_facet_field("sender", FacetOptions::default()),
+            month: schema_builder.add_facet_field("month", FacetOptions::default()),
+            attachment: schema_builder.add_text_field(
+                "attachment",
+                TextOptions::default().set_indexing_options(
+                    TextFieldIndexing::default()
+                        .set_tokenizer(LANG_CN)
+                        .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+                ),
+            ),
+            attachment_id: schema_builder.add_i64_field("attachment_id", STORED),
             custom: custom_field,
+            metadata: vec![],
+            metadata_codec: MetadataCodec::default(),
             schema: schema_builder.build(),
         }
     }
 }
 
+impl Fields {
+    pub fn entity(&self, kind: EntityKind) -> Field {
+        self.entities
+            .iter()
+            .find(|(entity_kind, _)| *entity_kind == kind)
+            .map(|(_, field)| *field)
+            .expect("every entity kind has a field")
+    }
+}
+
+impl Fields {
+    /// index the values at the dot separated keys of the metadata, searched with the content,
+    /// the field of `reply.id` is `meta_reply_id`
+    pub fn with_metadata(codec: MetadataCodec, keys: &[String]) -> Self {
+        let options = TextOptions::default().set_indexing_options(
+            TextFieldIndexing::default()
+                .set_tokenizer(LANG_CN)
+                .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+        );
+        let mut fields = Self::new(
+            keys.iter()
+                .map(|key| (metadata_field_name(key), options.clone()))
+                .collect(),
+        );
+        fields.metadata = keys.iter().cloned().zip(fields.custom.clone()).collect();
+        fields.metadata_codec = codec;
+        fields
+    }
+}
+
+pub fn metadata_field_name(key: &str) -> String {
+    format!(
+        "meta_{}",
+        key.chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect::<String>()
+    )
+}
+
+fn add_metadata_value(doc: &mut TantivyDocument, field: Field, value: &serde_json::Value) {
+    use serde_json::Value;
+    match value {
+        Value::String(value) => doc.add_text(field, value),
+        Value::Number(value) => doc.add_text(field, value.to_string()),
+        Value::Bool(value) => doc.add_text(field, value.to_string()),
+        Value::Array(values) => {
+            for value in values {
+                add_metadata_value(doc, field, value);
+            }
+        }
+        Value::Null | Value::Object(_) => {}
+    }
+}
+
 impl Default for Fields {
     fn default() -> Self {
         Self::new::<String>(vec![])
@@ -46,10 +152,59 @@ pub trait GetDocument {
 
 impl GetDocument for Record {
     fn get_document(&self, fields: &Fields) -> ChatRecordResult<TantivyDocument> {
-        Ok(doc! {
+        let mut doc = doc! {
             fields.idx => self.get_id() as i64,
             fields.content => self.content.as_str(),
             fields.timestamp => self.timestamp
+        };
+        if let Some(pinyin) = fields.pinyin {
+            doc.add_text(pinyin, &self.content);
+        }
+        doc.add_facet(
+            fields.chat,
+            Facet::from_path(vec![&self.chat_type, &self.group_id]),
+        );
+        doc.add_facet(
+            fields.sender,
+            Facet::from_path(vec![&self.chat_type, &self.sender_id]),
+        );
+        if let Some(time) = chrono::DateTime::from_timestamp_millis(self.timestamp) {
+            doc.add_facet(
+                fields.month,
+                Facet::from_path(vec![time.format("%Y-%m").to_string()]),
+            );
+        }
+        if !fields.metadata.is_empty() {
+            // metadata which can not be decoded is not indexed
+            if let Ok(Some(metadata)) =
+                self.get_metadata::<serde_json::Value>(fields.metadata_codec)
+            {
+                for (key, field) in &fields.metadata {
+                    if let Some(value) = key
+                        .split('.')
+                        .try_fold(&metadata, |value, key| value.get(key))
+                    {
+                        add_metadata_value(&mut doc, *field, value);
+                    }
+                }
+            }
+        }
+        let entities = Entities::extract(&self.content);
+        for (kind, field) in &fields.entities {
+            for value in entities.get(*kind) {
+                doc.add_text(*field, value);
+            }
+        }
+        Ok(doc)
+    }
+}
+
+impl GetDocument for AttachmentText {
+    fn get_document(&self, fields: &Fields) -> ChatRecordResult<TantivyDocument> {
+        Ok(doc! {
+            fields.idx => self.attachment.record_id as i64,
+            fields.attachment_id => self.attachment.get_id() as i64,
+            fields.attachment => self.text.as_str()
         })
     }
 }
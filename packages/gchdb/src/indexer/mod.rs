@@ -1,9 +1,19 @@
 mod content_indexer;
+mod extractor;
 mod fields;
+#[cfg(feature = "pinyin")]
+mod pinyin_filter;
+#[cfg(feature = "zh-convert")]
+mod simplified;
 mod tokenizers;
+mod vector_indexer;
 
 use super::*;
 use fields::{Fields, GetDocument};
-use tokenizers::{tokenizers_register, LANG_CN};
+use tokenizers::{tokenizers_register, LANG_CN, LANG_CN_PINYIN};
 
 pub use content_indexer::ContentIndexer;
+#[cfg(feature = "pdf")]
+pub use extractor::PdfExtractor;
+pub use extractor::{guess_mime, HtmlExtractor, PlainTextExtractor, TextExtractor, TextExtractors};
+pub use vector_indexer::VectorIndexer;
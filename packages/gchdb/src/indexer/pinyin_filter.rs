@@ -0,0 +1,87 @@
+use pinyin::ToPinyin;
+use tantivy::tokenizer::{Token, TokenFilter, TokenStream, Tokenizer};
+
+/// emit the full pinyin and the initials of chinese tokens at the position of the token
+#[derive(Clone)]
+pub struct PinyinFilter;
+
+impl TokenFilter for PinyinFilter {
+    type Tokenizer<T: Tokenizer> = PinyinFilterWrapper<T>;
+
+    fn transform<T: Tokenizer>(self, tokenizer: T) -> Self::Tokenizer<T> {
+        PinyinFilterWrapper(tokenizer)
+    }
+}
+
+#[derive(Clone)]
+pub struct PinyinFilterWrapper<T>(T);
+
+impl<T: Tokenizer> Tokenizer for PinyinFilterWrapper<T> {
+    type TokenStream<'a> = PinyinTokenStream<T::TokenStream<'a>>;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> Self::TokenStream<'a> {
+        PinyinTokenStream {
+            tail: self.0.token_stream(text),
+            pending: vec![],
+            token: Token::default(),
+        }
+    }
+}
+
+pub struct PinyinTokenStream<T> {
+    tail: T,
+    pending: Vec<Token>,
+    token: Token,
+}
+
+impl<T: TokenStream> PinyinTokenStream<T> {
+    fn expand(&mut self) {
+        if self.token.text.is_ascii() {
+            return;
+        }
+        let (plain, initials) = self.token.text.as_str().to_pinyin().flatten().fold(
+            (String::new(), String::new()),
+            |(mut plain, mut initials), pinyin| {
+                plain.push_str(pinyin.plain());
+                initials.push_str(pinyin.first_letter());
+                (plain, initials)
+            },
+        );
+        if plain.is_empty() {
+            return;
+        }
+        if initials != plain {
+            self.pending.push(Token {
+                text: initials,
+                ..self.token.clone()
+            });
+        }
+        self.pending.push(Token {
+            text: plain,
+            ..self.token.clone()
+        });
+    }
+}
+
+impl<T: TokenStream> TokenStream for PinyinTokenStream<T> {
+    fn advance(&mut self) -> bool {
+        if let Some(token) = self.pending.pop() {
+            self.token = token;
+            return true;
+        }
+        if !self.tail.advance() {
+            return false;
+        }
+        self.token = self.tail.token().clone();
+        self.expand();
+        true
+    }
+
+    fn token(&self) -> &Token {
+        &self.token
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.token
+    }
+}
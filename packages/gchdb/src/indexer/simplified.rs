@@ -0,0 +1,28 @@
+use tantivy::tokenizer::Tokenizer;
+use zhconv::{zhconv, Variant};
+
+/// convert traditional chinese text to simplified chinese before tokenizing,
+/// so both are segmented the same way
+#[derive(Clone)]
+pub struct SimplifiedTokenizer<T> {
+    tokenizer: T,
+    buffer: String,
+}
+
+impl<T> SimplifiedTokenizer<T> {
+    pub fn new(tokenizer: T) -> Self {
+        Self {
+            tokenizer,
+            buffer: String::new(),
+        }
+    }
+}
+
+impl<T: Tokenizer> Tokenizer for SimplifiedTokenizer<T> {
+    type TokenStream<'a> = T::TokenStream<'a>;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> Self::TokenStream<'a> {
+        self.buffer = zhconv(text, Variant::ZhHans);
+        self.tokenizer.token_stream(&self.buffer)
+    }
+}
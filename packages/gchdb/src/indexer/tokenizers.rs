@@ -1,17 +1,32 @@
 use cang_jie::{CangJieTokenizer, TokenizerOption};
 // use lindera_tantivy::tokenizer::LinderaTokenizer;
-use tantivy::tokenizer::TokenizerManager;
+#[cfg(feature = "pinyin")]
+use super::pinyin_filter::PinyinFilter;
+#[cfg(feature = "zh-convert")]
+use super::simplified::SimplifiedTokenizer;
+use tantivy::tokenizer::{TextAnalyzer, TextAnalyzerBuilder, TokenizerManager};
 
 pub use cang_jie::CANG_JIE as LANG_CN;
 // pub const LANG_JP: &str = "lindera";
+pub const LANG_CN_PINYIN: &str = "cang_jie_pinyin";
+
+/// traditional chinese is normalized to simplified if `zh-convert` is enabled
+fn cang_jie() -> TextAnalyzerBuilder {
+    let tokenizer = CangJieTokenizer {
+        option: TokenizerOption::ForSearch { hmm: true },
+        ..Default::default()
+    };
+    #[cfg(feature = "zh-convert")]
+    let tokenizer = SimplifiedTokenizer::new(tokenizer);
+    TextAnalyzer::builder(tokenizer).dynamic()
+}
 
 pub fn tokenizers_register(tokenizers: &TokenizerManager) {
+    tokenizers.register(LANG_CN, cang_jie().build());
+    #[cfg(feature = "pinyin")]
     tokenizers.register(
-        LANG_CN,
-        CangJieTokenizer {
-            option: TokenizerOption::ForSearch { hmm: true },
-            ..Default::default()
-        },
+        LANG_CN_PINYIN,
+        cang_jie().filter_dynamic(PinyinFilter).build(),
     );
     // tokenizers.register(LANG_JP, LinderaTokenizer::new("decompose", ""));
 }
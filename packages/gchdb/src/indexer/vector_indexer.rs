@@ -0,0 +1,109 @@
+use std::collections::{HashMap, HashSet};
+
+/// in-memory embeddings of records, searched by a flat scan of cosine similarity
+#[derive(Default)]
+pub struct VectorIndexer {
+    vectors: HashMap<i32, Vec<f32>>,
+}
+
+fn normalize(vector: &[f32]) -> Option<Vec<f32>> {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 && norm.is_finite() {
+        Some(vector.iter().map(|v| v / norm).collect())
+    } else {
+        None
+    }
+}
+
+impl VectorIndexer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the dimension of the stored vectors of other records
+    fn dimension(&self, record_id: i32) -> Option<usize> {
+        self.vectors
+            .iter()
+            .find(|(id, _)| **id != record_id)
+            .map(|(_, vector)| vector.len())
+    }
+
+    /// empty, zero and non-finite vectors and vectors of another dimension than the stored ones
+    /// are rejected and remove the stale vector of the record, returns whether the vector is stored
+    pub fn insert(&mut self, record_id: i32, vector: &[f32]) -> bool {
+        let dimension = self.dimension(record_id).unwrap_or(vector.len());
+        match normalize(vector).filter(|vector| vector.len() == dimension) {
+            Some(vector) => {
+                self.vectors.insert(record_id, vector);
+                true
+            }
+            None => {
+                self.vectors.remove(&record_id);
+                false
+            }
+        }
+    }
+
+    pub fn remove(&mut self, record_id: i32) {
+        self.vectors.remove(&record_id);
+    }
+
+    pub fn clear(&mut self) {
+        self.vectors.clear();
+    }
+
+    pub fn contains(&self, record_id: i32) -> bool {
+        self.vectors.contains_key(&record_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.vectors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vectors.is_empty()
+    }
+
+    /// the normalized vector of the record
+    pub fn get(&self, record_id: i32) -> Option<&[f32]> {
+        self.vectors.get(&record_id).map(Vec::as_slice)
+    }
+
+    /// ids and cosine similarities of the nearest records in descending order of similarity,
+    /// only records in `ids` are searched if it is some, vectors of other dimensions are skipped
+    pub fn search(
+        &self,
+        vector: &[f32],
+        ids: Option<&[i32]>,
+        exclude: Option<i32>,
+        limit: usize,
+    ) -> Vec<(i32, f32)> {
+        let vector = match normalize(vector) {
+            Some(vector) => vector,
+            None => return vec![],
+        };
+        let score = |(record_id, other): (&i32, &Vec<f32>)| {
+            if other.len() == vector.len() && Some(*record_id) != exclude {
+                Some((
+                    *record_id,
+                    vector.iter().zip(other).map(|(a, b)| a * b).sum(),
+                ))
+            } else {
+                None
+            }
+        };
+        let mut scores = match ids {
+            Some(ids) => ids
+                .iter()
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .filter_map(|record_id| self.vectors.get_key_value(record_id))
+                .filter_map(score)
+                .collect::<Vec<(i32, f32)>>(),
+            None => self.vectors.iter().filter_map(score).collect(),
+        };
+        scores.sort_by(|(a_id, a), (b_id, b)| b.total_cmp(a).then(a_id.cmp(b_id)));
+        scores.truncate(limit);
+        scores
+    }
+}
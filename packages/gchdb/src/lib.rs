@@ -14,8 +14,21 @@ mod utils;
 use types::*;
 use utils::*;
 
-pub use adapter::SqliteChatRecorder;
-pub use indexer::ContentIndexer;
+pub use adapter::{RecordIter, SqliteChatRecorder};
+#[cfg(feature = "pdf")]
+pub use indexer::PdfExtractor;
+pub use indexer::{
+    guess_mime, ContentIndexer, HtmlExtractor, PlainTextExtractor, TextExtractor, TextExtractors,
+    VectorIndexer,
+};
 pub use types::{
-    Attachments, Blob, ChatRecorder, ChatRecordError, MetadataMerger, Query, Record, RecordType,
+    deep_merge_metadata, keep_new_metadata, keep_old_metadata, prefer_non_empty_metadata,
+    union_metadata, Attachment, AttachmentHit, AttachmentPolicy, AttachmentReport, Attachments,
+    Blob, ChatRecordError, ChatRecorder, Conversation, ConversationSummary, Cursor,
+    CursorDirection, DuplicateCluster, DuplicateSpread, Embedder, Embedding, Entities, EntityCount,
+    EntityKind, FacetCount, FacetCounts, FacetField, FacetedRecords, Fingerprint, HistogramBucket,
+    HistogramInterval, JsonMerge, JsonMerger, MergedMetadata, MetadataCodec, MetadataFilter,
+    MetadataMerger, MoreLikeThisOptions, Participant, Person, PersonIdentity, Query, QueryExpr,
+    QuerySort, Record, RecordContext, RecordPage, RecordStats, RecordType, ScoredRecord,
+    SearchOperator, SearchRequest, Sender, SenderAlias, StatsCount, StatsGroup, StringFilter,
 };
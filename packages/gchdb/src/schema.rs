@@ -7,6 +7,14 @@ table! {
     }
 }
 
+table! {
+    attachment_texts (hash, name) {
+        hash -> BigInt,
+        name -> Text,
+        text -> Nullable<Text>,
+    }
+}
+
 table! {
     blobs (hash) {
         hash -> BigInt,
@@ -14,6 +22,66 @@ table! {
     }
 }
 
+table! {
+    conversations (id) {
+        id -> Nullable<Integer>,
+        chat_type -> Text,
+        owner_id -> Text,
+        group_id -> Text,
+        name -> Text,
+        avatar -> Nullable<BigInt>,
+        metadata -> Nullable<Binary>,
+    }
+}
+
+table! {
+    embeddings (id) {
+        id -> Nullable<Integer>,
+        record_id -> Integer,
+        vector -> Binary,
+    }
+}
+
+table! {
+    fingerprints (id) {
+        id -> Nullable<Integer>,
+        record_id -> Integer,
+        simhash -> BigInt,
+        band0 -> Integer,
+        band1 -> Integer,
+        band2 -> Integer,
+        band3 -> Integer,
+    }
+}
+
+table! {
+    participants (id) {
+        id -> Nullable<Integer>,
+        conversation_id -> Integer,
+        sender_id -> Text,
+        display_name -> Text,
+        joined_at -> Nullable<BigInt>,
+        left_at -> Nullable<BigInt>,
+    }
+}
+
+table! {
+    person_identities (id) {
+        id -> Nullable<Integer>,
+        person_id -> Integer,
+        chat_type -> Text,
+        sender_id -> Text,
+    }
+}
+
+table! {
+    persons (id) {
+        id -> Nullable<Integer>,
+        name -> Text,
+        metadata -> Nullable<Binary>,
+    }
+}
+
 table! {
     records (id) {
         id -> Nullable<Integer>,
@@ -28,8 +96,37 @@ table! {
     }
 }
 
+table! {
+    sender_aliases (id) {
+        id -> Nullable<Integer>,
+        chat_type -> Text,
+        sender_id -> Text,
+        name -> Text,
+        timestamp -> BigInt,
+    }
+}
+
+table! {
+    senders (id) {
+        id -> Nullable<Integer>,
+        chat_type -> Text,
+        sender_id -> Text,
+        name -> Text,
+        timestamp -> BigInt,
+    }
+}
+
 allow_tables_to_appear_in_same_query!(
+    attachment_texts,
     attachments,
     blobs,
+    conversations,
+    embeddings,
+    fingerprints,
+    participants,
+    person_identities,
+    persons,
     records,
+    sender_aliases,
+    senders,
 );
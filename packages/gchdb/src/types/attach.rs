@@ -31,3 +31,54 @@ impl<'a> Attachment {
         self.id.unwrap_or_default()
     }
 }
+
+/// text extracted from an attachment, indexed next to the records
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AttachmentText {
+    pub attachment: Attachment,
+    pub text: String,
+}
+
+/// an attachment whose extracted text is matched by a search, with the record owning it
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct AttachmentHit {
+    pub record: Record,
+    pub attachment: Attachment,
+}
+
+/// how the attachments of a record are merged with the stored attachments when it is updated
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AttachmentPolicy {
+    /// the stored attachments missing from the new set are removed
+    ReplaceAll,
+    /// attachments with the same name are replaced, the others are kept
+    #[default]
+    MergeByName,
+    /// attachments with the same name are kept, only new names are added
+    KeepExisting,
+    /// changed attachments with the same name are added as `name (1).ext`, `name (2).ext`, ...
+    AppendVersioned,
+}
+
+impl AttachmentPolicy {
+    /// name of the version of the attachment, e.g. `photo (1).jpg`
+    pub fn versioned_name(name: &str, version: usize) -> String {
+        match name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => {
+                format!("{} ({}).{}", stem, version, extension)
+            }
+            _ => format!("{} ({})", name, version),
+        }
+    }
+}
+
+/// names of the attachments changed by inserting or updating a record, in ascending order
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AttachmentReport {
+    pub added: Vec<String>,
+    pub replaced: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub removed: Vec<String>,
+    /// attachments which could not be written
+    pub failed: Vec<String>,
+}
@@ -0,0 +1,51 @@
+use super::*;
+
+#[derive(Queryable, Insertable, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[diesel(table_name = conversations)]
+pub struct Conversation {
+    pub id: Option<i32>,
+    pub chat_type: String,
+    pub owner_id: String,
+    pub group_id: String,
+    pub name: String,
+    pub avatar: Option<i64>,
+    pub metadata: Option<Vec<u8>>,
+}
+
+impl Conversation {
+    pub fn get_id(&self) -> i32 {
+        self.id.unwrap_or_default()
+    }
+}
+
+#[derive(Queryable, Insertable, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[diesel(table_name = participants)]
+pub struct Participant {
+    pub id: Option<i32>,
+    pub conversation_id: i32,
+    pub sender_id: String,
+    pub display_name: String,
+    pub joined_at: Option<i64>,
+    pub left_at: Option<i64>,
+}
+
+impl Participant {
+    pub fn get_id(&self) -> i32 {
+        self.id.unwrap_or_default()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.left_at.is_none()
+    }
+}
+
+/// one inbox entry, `conversation` is none if no conversation row was stored for the group
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ConversationSummary {
+    pub chat_type: String,
+    pub owner_id: String,
+    pub group_id: String,
+    pub conversation: Option<Conversation>,
+    pub message_count: i64,
+    pub last_message: Option<i64>,
+}
@@ -0,0 +1,97 @@
+use super::*;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CursorDirection {
+    After,
+    Before,
+}
+
+/// keyset pagination position on `(timestamp, id)`,
+/// use `to_string` and `parse` to pass it around as an opaque token
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cursor {
+    pub direction: CursorDirection,
+    pub timestamp: i64,
+    pub id: i32,
+}
+
+impl Cursor {
+    pub fn after(record: &Record) -> Self {
+        Self {
+            direction: CursorDirection::After,
+            timestamp: record.timestamp,
+            id: record.get_id(),
+        }
+    }
+
+    pub fn before(record: &Record) -> Self {
+        Self {
+            direction: CursorDirection::Before,
+            timestamp: record.timestamp,
+            id: record.get_id(),
+        }
+    }
+
+    /// page forward from the oldest record
+    pub fn oldest() -> Self {
+        Self {
+            direction: CursorDirection::After,
+            timestamp: i64::MIN,
+            id: i32::MIN,
+        }
+    }
+
+    /// page backward from the newest record
+    pub fn newest() -> Self {
+        Self {
+            direction: CursorDirection::Before,
+            timestamp: i64::MAX,
+            id: i32::MAX,
+        }
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{:x}.{:x}",
+            match self.direction {
+                CursorDirection::After => 'a',
+                CursorDirection::Before => 'b',
+            },
+            self.timestamp as u64,
+            self.id as u32
+        )
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = ChatRecordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ChatRecordError::InvalidQuery(format!("invalid cursor: {}", s));
+        let direction = match s.get(..1) {
+            Some("a") => CursorDirection::After,
+            Some("b") => CursorDirection::Before,
+            _ => return Err(invalid()),
+        };
+        let (timestamp, id) = s[1..].split_once('.').ok_or_else(invalid)?;
+        Ok(Self {
+            direction,
+            timestamp: u64::from_str_radix(timestamp, 16).map_err(|_| invalid())? as i64,
+            id: u32::from_str_radix(id, 16).map_err(|_| invalid())? as i32,
+        })
+    }
+}
+
+/// records are always in ascending order of `(timestamp, id)`,
+/// `prev` and `next` are none if there is no more record in that direction
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RecordPage {
+    pub records: Vec<Record>,
+    pub prev: Option<Cursor>,
+    pub next: Option<Cursor>,
+}
@@ -0,0 +1,37 @@
+use super::*;
+
+/// user-supplied embedding function, returns none if the content can not be embedded
+pub type Embedder = Box<dyn Fn(&str) -> Option<Vec<f32>> + Send + Sync>;
+
+/// the embedding of a record, the vector is stored as little-endian `f32`
+#[derive(Queryable, Insertable, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[diesel(table_name = embeddings)]
+pub struct Embedding {
+    pub id: Option<i32>,
+    pub record_id: i32,
+    pub vector: Vec<u8>,
+}
+
+impl Embedding {
+    pub fn new(record_id: i32, vector: &[f32]) -> Self {
+        Self {
+            id: None,
+            record_id,
+            vector: vector.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        }
+    }
+
+    pub fn get_vector(&self) -> Vec<f32> {
+        self.vector
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect()
+    }
+}
+
+/// a record with the similarity or fused rank score it is sorted by, higher is better
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ScoredRecord {
+    pub record: Record,
+    pub score: f32,
+}
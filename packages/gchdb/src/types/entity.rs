@@ -0,0 +1,164 @@
+use super::*;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EntityKind {
+    Mention,
+    Url,
+    /// host of the url, without `www.`
+    Domain,
+    Hashtag,
+    Emoji,
+}
+
+impl EntityKind {
+    pub fn all() -> [EntityKind; 5] {
+        [
+            EntityKind::Mention,
+            EntityKind::Url,
+            EntityKind::Domain,
+            EntityKind::Hashtag,
+            EntityKind::Emoji,
+        ]
+    }
+
+    pub(crate) fn field_name(&self) -> &'static str {
+        match self {
+            EntityKind::Mention => "mention",
+            EntityKind::Url => "url",
+            EntityKind::Domain => "domain",
+            EntityKind::Hashtag => "hashtag",
+            EntityKind::Emoji => "emoji",
+        }
+    }
+
+    /// the form stored in the index, `@` and `#` are stripped, hashtags and domains are lowercased
+    pub fn normalize(&self, value: &str) -> String {
+        match self {
+            EntityKind::Mention => value.trim_start_matches('@').into(),
+            EntityKind::Hashtag => value.trim_matches('#').to_lowercase(),
+            EntityKind::Domain => {
+                let domain = value.to_lowercase();
+                domain
+                    .strip_prefix("www.")
+                    .map(Into::into)
+                    .unwrap_or(domain)
+            }
+            EntityKind::Url | EntityKind::Emoji => value.into(),
+        }
+    }
+}
+
+/// mentions, urls, hashtags and emoji in the content of a record
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Entities {
+    pub mentions: Vec<String>,
+    pub urls: Vec<String>,
+    pub domains: Vec<String>,
+    pub hashtags: Vec<String>,
+    pub emoji: Vec<String>,
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32, 0x1F000..=0x1F3FA | 0x1F400..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF)
+}
+
+fn is_url_char(c: char) -> bool {
+    c.is_ascii_graphic() && !matches!(c, '<' | '>' | '"' | '\'' | '`' | '(' | ')' | '[' | ']')
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl Entities {
+    pub fn extract(content: &str) -> Self {
+        let mut entities = Self::default();
+        let chars = content.chars().collect::<Vec<_>>();
+        let take_while = |start: usize, pred: &dyn Fn(char) -> bool| {
+            let end = (start..chars.len())
+                .find(|i| !pred(chars[*i]))
+                .unwrap_or(chars.len());
+            (chars[start..end].iter().collect::<String>(), end)
+        };
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let boundary = i == 0 || !(is_word_char(chars[i - 1]) || chars[i - 1] == '/');
+            if (c == 'h' || c == 'H') && boundary {
+                let rest = chars[i..chars.len().min(i + 8)]
+                    .iter()
+                    .collect::<String>()
+                    .to_lowercase();
+                if rest.starts_with("http://") || rest.starts_with("https://") {
+                    let (url, end) = take_while(i, &is_url_char);
+                    let url = url.trim_end_matches(['.', ',', ';', ':', '!', '?']);
+                    let host = url
+                        .split_once("://")
+                        .and_then(|(_, rest)| rest.split(['/', '?', '#']).next())
+                        .map(|host| host.rsplit('@').next().unwrap_or(host))
+                        .map(|host| host.split(':').next().unwrap_or(host))
+                        .unwrap_or_default();
+                    if !host.is_empty() {
+                        push_unique(&mut entities.domains, EntityKind::Domain.normalize(host));
+                        push_unique(&mut entities.urls, url.into());
+                    }
+                    i = end.max(i + 1);
+                    continue;
+                }
+            }
+            if c == '@' && boundary {
+                let (mention, end) =
+                    take_while(i + 1, &|c| is_word_char(c) || matches!(c, '-' | '.'));
+                let mention = mention.trim_end_matches(['-', '.']);
+                if !mention.is_empty() {
+                    push_unique(&mut entities.mentions, mention.into());
+                }
+                i = end;
+                continue;
+            }
+            if c == '#' && boundary {
+                let (hashtag, end) = take_while(i + 1, &is_word_char);
+                if !hashtag.is_empty() && !hashtag.chars().all(|c| c.is_ascii_digit()) {
+                    push_unique(
+                        &mut entities.hashtags,
+                        EntityKind::Hashtag.normalize(&hashtag),
+                    );
+                }
+                // the closing `#` of `#topic#`
+                i = if chars.get(end) == Some(&'#') {
+                    end + 1
+                } else {
+                    end
+                };
+                continue;
+            }
+            if is_emoji(c) {
+                push_unique(&mut entities.emoji, c.to_string());
+            }
+            i += 1;
+        }
+        entities
+    }
+
+    pub fn get(&self, kind: EntityKind) -> &[String] {
+        match kind {
+            EntityKind::Mention => &self.mentions,
+            EntityKind::Url => &self.urls,
+            EntityKind::Domain => &self.domains,
+            EntityKind::Hashtag => &self.hashtags,
+            EntityKind::Emoji => &self.emoji,
+        }
+    }
+}
+
+fn push_unique(values: &mut Vec<String>, value: String) {
+    if !values.contains(&value) {
+        values.push(value);
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct EntityCount {
+    pub value: String,
+    pub count: u64,
+}
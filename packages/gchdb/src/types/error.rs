@@ -12,6 +12,12 @@ pub enum ChatRecordError {
     TantivyError(TantivyError),
     #[error("{0:?}")]
     TantivyQueryError(QueryParserError),
+    #[error("{0}")]
+    InvalidQuery(String),
+    #[error("{0}")]
+    MetadataError(String),
+    #[error("metadata conflict: {0}")]
+    MetadataConflict(String),
     #[error(transparent)]
     ContextError(#[from] anyhow::Error),
 }
@@ -0,0 +1,75 @@
+use super::*;
+use std::ops::{BitAnd, BitOr, Not};
+
+/// boolean expression over record fields and keyword search,
+/// can be combined with `&`, `|` and `!`
+#[derive(Clone, Debug, PartialEq)]
+pub enum QueryExpr {
+    And(Vec<QueryExpr>),
+    Or(Vec<QueryExpr>),
+    Not(Box<QueryExpr>),
+    ChatType(StringFilter),
+    OwnerId(StringFilter),
+    GroupId(StringFilter),
+    SenderId(StringFilter),
+    SenderName(StringFilter),
+    /// sent by any identity linked to the person
+    Person(i32),
+    Ids(Vec<i32>),
+    HasAttachments,
+    /// json path filter of the metadata
+    Metadata(MetadataFilter),
+    /// timestamp is less than or equal to
+    Before(i64),
+    /// timestamp is greater than or equal to
+    After(i64),
+    /// full-text query of the indexer
+    Keyword(SearchRequest),
+}
+
+impl BitAnd for QueryExpr {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        match (self, rhs) {
+            (Self::And(mut lhs), Self::And(rhs)) => {
+                lhs.extend(rhs);
+                Self::And(lhs)
+            }
+            (Self::And(mut lhs), rhs) => {
+                lhs.push(rhs);
+                Self::And(lhs)
+            }
+            (lhs, rhs) => Self::And(vec![lhs, rhs]),
+        }
+    }
+}
+
+impl BitOr for QueryExpr {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        match (self, rhs) {
+            (Self::Or(mut lhs), Self::Or(rhs)) => {
+                lhs.extend(rhs);
+                Self::Or(lhs)
+            }
+            (Self::Or(mut lhs), rhs) => {
+                lhs.push(rhs);
+                Self::Or(lhs)
+            }
+            (lhs, rhs) => Self::Or(vec![lhs, rhs]),
+        }
+    }
+}
+
+impl Not for QueryExpr {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        match self {
+            Self::Not(expr) => *expr,
+            expr => Self::Not(Box::new(expr)),
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use super::*;
+
+/// breakdown of search hits
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FacetField {
+    ChatType,
+    /// groups of the chat type, or of every chat type if none
+    Group(Option<String>),
+    /// senders of the chat type, or of every chat type if none
+    Sender(Option<String>),
+    /// `yyyy-mm` of the timestamp in utc
+    Month,
+}
+
+/// `path` is `[chat_type, group_id]` for groups, `[chat_type, sender_id]` for senders,
+/// and a single value for chat types and months
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FacetCount {
+    pub path: Vec<String>,
+    pub count: u64,
+}
+
+pub type FacetCounts = Vec<(FacetField, Vec<FacetCount>)>;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct FacetedRecords {
+    pub records: Vec<Record>,
+    pub facets: FacetCounts,
+}
@@ -0,0 +1,121 @@
+use super::*;
+
+/// normalized contents shorter than this are not fingerprinted, so short replies are not duplicates
+const MIN_CHARS: usize = 10;
+const SHINGLE_CHARS: usize = 3;
+
+/// 64-bit simhash of the normalized content of a record, split into four 16-bit bands,
+/// fingerprints within 3 bits of each other share at least one band
+#[derive(Queryable, Insertable, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[diesel(table_name = fingerprints)]
+pub struct Fingerprint {
+    pub id: Option<i32>,
+    pub record_id: i32,
+    pub simhash: i64,
+    pub band0: i32,
+    pub band1: i32,
+    pub band2: i32,
+    pub band3: i32,
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl Fingerprint {
+    /// returns none if the content is too short, case, whitespace and punctuation are ignored
+    pub fn new(record_id: i32, content: &str) -> Option<Self> {
+        let simhash = Self::simhash(content)?;
+        let band = |i: u32| (simhash >> (16 * i) & 0xffff) as i32;
+        Some(Self {
+            id: None,
+            record_id,
+            simhash: simhash as i64,
+            band0: band(0),
+            band1: band(1),
+            band2: band(2),
+            band3: band(3),
+        })
+    }
+
+    /// simhash of the character shingles of the content
+    pub fn simhash(content: &str) -> Option<u64> {
+        let chars = content
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect::<Vec<_>>();
+        if chars.len() < MIN_CHARS {
+            return None;
+        }
+        let mut weights = [0i32; 64];
+        for shingle in chars.windows(SHINGLE_CHARS) {
+            let hash = fnv1a(shingle.iter().collect::<String>().as_bytes());
+            for (i, weight) in weights.iter_mut().enumerate() {
+                *weight += if hash >> i & 1 == 1 { 1 } else { -1 };
+            }
+        }
+        Some(
+            weights
+                .iter()
+                .enumerate()
+                .filter(|(_, weight)| **weight > 0)
+                .fold(0, |simhash, (i, _)| simhash | 1 << i),
+        )
+    }
+
+    pub fn bands(&self) -> [i32; 4] {
+        [self.band0, self.band1, self.band2, self.band3]
+    }
+
+    /// number of differing bits
+    pub fn distance(&self, other: &Self) -> u32 {
+        (self.simhash ^ other.simhash).count_ones()
+    }
+}
+
+/// near-duplicate records in ascending order of time, the first is the earliest occurrence
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct DuplicateCluster {
+    pub records: Vec<Record>,
+}
+
+/// the appearances of a duplicated message in a group
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DuplicateSpread {
+    pub chat_type: String,
+    pub group_id: String,
+    pub first: i64,
+    pub last: i64,
+    pub count: u64,
+}
+
+impl DuplicateCluster {
+    pub fn earliest(&self) -> Option<&Record> {
+        self.records.first()
+    }
+
+    /// groups the message appeared in, in order of first appearance
+    pub fn spread(&self) -> Vec<DuplicateSpread> {
+        let mut spread: Vec<DuplicateSpread> = vec![];
+        let mut positions = HashMap::new();
+        for record in &self.records {
+            let position = *positions
+                .entry((&record.chat_type, &record.group_id))
+                .or_insert_with(|| {
+                    spread.push(DuplicateSpread {
+                        chat_type: record.chat_type.clone(),
+                        group_id: record.group_id.clone(),
+                        first: record.timestamp,
+                        ..Default::default()
+                    });
+                    spread.len() - 1
+                });
+            spread[position].last = record.timestamp;
+            spread[position].count += 1;
+        }
+        spread
+    }
+}
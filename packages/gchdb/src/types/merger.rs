@@ -0,0 +1,250 @@
+use super::*;
+use serde_json::{Map, Value};
+
+/// the result of merging the stored metadata of a record with the new metadata
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MergedMetadata {
+    Merged(Vec<u8>),
+    /// the metadata of the record is removed
+    Removed,
+    /// the record is not updated and `ChatRecordError::MetadataConflict` is returned
+    Conflict(String),
+}
+
+impl From<Option<Vec<u8>>> for MergedMetadata {
+    fn from(metadata: Option<Vec<u8>>) -> Self {
+        match metadata {
+            Some(metadata) => Self::Merged(metadata),
+            None => Self::Removed,
+        }
+    }
+}
+
+/// merges the stored metadata of a record with the new metadata when the record is updated,
+/// implemented by closures taking the recorder, the new attachments, the old and the new metadata
+pub trait MetadataMerger<C: ?Sized> {
+    fn merge(
+        &mut self,
+        recorder: &C,
+        attachs: &Attachments,
+        old: Vec<u8>,
+        new: Vec<u8>,
+    ) -> MergedMetadata;
+}
+
+impl<C, F, M> MetadataMerger<C> for F
+where
+    C: ?Sized,
+    F: FnMut(&C, &Attachments, Vec<u8>, Vec<u8>) -> M,
+    M: Into<MergedMetadata>,
+{
+    fn merge(
+        &mut self,
+        recorder: &C,
+        attachs: &Attachments,
+        old: Vec<u8>,
+        new: Vec<u8>,
+    ) -> MergedMetadata {
+        self(recorder, attachs, old, new).into()
+    }
+}
+
+/// how a json value of the old metadata is merged with the new one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JsonMerge {
+    KeepOld,
+    KeepNew,
+    /// objects are merged key by key recursively, other values are replaced by the new value
+    Deep,
+    /// arrays are concatenated without duplicates, objects are merged key by key,
+    /// other values are replaced by the new value
+    Union,
+    /// the new value unless it is null or an empty string, array or object
+    PreferNonEmpty,
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(value) => value.is_empty(),
+        Value::Array(values) => values.is_empty(),
+        Value::Object(values) => values.is_empty(),
+        Value::Bool(_) | Value::Number(_) => false,
+    }
+}
+
+/// merges json metadata objects key by key, values at the dot separated keys are merged by their
+/// strategies, other values by the strategy of the closest parent key or the default strategy,
+/// objects containing keys with their own strategy are merged key by key whatever their strategy
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JsonMerger {
+    default: JsonMerge,
+    keys: Vec<(String, JsonMerge)>,
+}
+
+impl JsonMerger {
+    pub fn new(default: JsonMerge) -> Self {
+        Self {
+            default,
+            keys: vec![],
+        }
+    }
+
+    pub fn key<S: Into<String>>(mut self, key: S, merge: JsonMerge) -> Self {
+        self.keys.push((key.into(), merge));
+        self
+    }
+
+    fn get(&self, path: &str) -> Option<JsonMerge> {
+        self.keys
+            .iter()
+            .find(|(key, _)| key == path)
+            .map(|(_, merge)| *merge)
+    }
+
+    /// whether a key below the path has its own strategy
+    fn has_nested(&self, path: &str) -> bool {
+        self.keys.iter().any(|(key, _)| {
+            path.is_empty() || (key.starts_with(path) && key[path.len()..].starts_with('.'))
+        })
+    }
+
+    pub fn merge_value(&self, old: Value, new: Value) -> Value {
+        self.apply("", old, new, self.default)
+    }
+
+    /// merge the encoded metadata, the new metadata is kept if either side can not be decoded
+    pub fn merge(&self, codec: MetadataCodec, old: Vec<u8>, new: Vec<u8>) -> Option<Vec<u8>> {
+        match (codec.decode(&old), codec.decode(&new)) {
+            (Ok(old), Ok(new)) => codec.encode(&self.merge_value(old, new)).ok(),
+            _ => Some(new),
+        }
+    }
+
+    /// keys only in the old object are dropped by `KeepNew`,
+    /// keys only in the new object are dropped by `KeepOld`
+    fn merge_object(
+        &self,
+        path: &str,
+        mut old: Map<String, Value>,
+        mut new: Map<String, Value>,
+        inherited: JsonMerge,
+    ) -> Map<String, Value> {
+        let keys = old
+            .keys()
+            .chain(new.keys().filter(|key| !old.contains_key(*key)))
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut merged = Map::new();
+        for key in keys {
+            let path = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", path, key)
+            };
+            let merge = self.get(&path).unwrap_or(inherited);
+            let value = match (old.remove(&key), new.remove(&key)) {
+                (Some(old), Some(new)) => Some(self.apply(&path, old, new, merge)),
+                (Some(_), None) if merge == JsonMerge::KeepNew => None,
+                (None, Some(_)) if merge == JsonMerge::KeepOld => None,
+                (old, new) => old.or(new),
+            };
+            if let Some(value) = value {
+                merged.insert(key, value);
+            }
+        }
+        merged
+    }
+
+    fn apply(&self, path: &str, old: Value, new: Value, merge: JsonMerge) -> Value {
+        match (merge, old, new) {
+            (JsonMerge::KeepOld, Value::Object(old), Value::Object(new))
+            | (JsonMerge::KeepNew, Value::Object(old), Value::Object(new))
+            | (JsonMerge::PreferNonEmpty, Value::Object(old), Value::Object(new))
+                if self.has_nested(path)
+                    && (merge != JsonMerge::PreferNonEmpty || !new.is_empty()) =>
+            {
+                Value::Object(self.merge_object(path, old, new, merge))
+            }
+            (JsonMerge::KeepOld, old, _) => old,
+            (JsonMerge::KeepNew, _, new) => new,
+            (JsonMerge::PreferNonEmpty, old, new) => {
+                if is_empty(&new) {
+                    old
+                } else {
+                    new
+                }
+            }
+            (JsonMerge::Deep, Value::Object(old), Value::Object(new))
+            | (JsonMerge::Union, Value::Object(old), Value::Object(new)) => {
+                Value::Object(self.merge_object(path, old, new, merge))
+            }
+            (JsonMerge::Union, Value::Array(mut old), Value::Array(new)) => {
+                for value in new {
+                    if !old.contains(&value) {
+                        old.push(value);
+                    }
+                }
+                Value::Array(old)
+            }
+            (JsonMerge::Deep, _, new) | (JsonMerge::Union, _, new) => new,
+        }
+    }
+}
+
+/// the old metadata is kept
+pub fn keep_old_metadata<C: ?Sized>(
+    _recorder: &C,
+    _attachs: &Attachments,
+    old: Vec<u8>,
+    _new: Vec<u8>,
+) -> Option<Vec<u8>> {
+    Some(old)
+}
+
+/// the new metadata replaces the old, this is also what happens if no merger is given,
+/// so keys only in the old metadata are lost
+pub fn keep_new_metadata<C: ?Sized>(
+    _recorder: &C,
+    _attachs: &Attachments,
+    _old: Vec<u8>,
+    new: Vec<u8>,
+) -> Option<Vec<u8>> {
+    Some(new)
+}
+
+/// json objects are merged recursively, the new metadata is kept if either side is not json
+pub fn deep_merge_metadata<C: ?Sized>(
+    _recorder: &C,
+    _attachs: &Attachments,
+    old: Vec<u8>,
+    new: Vec<u8>,
+) -> Option<Vec<u8>> {
+    JsonMerger::new(JsonMerge::Deep).merge(MetadataCodec::Json, old, new)
+}
+
+/// json arrays are concatenated without duplicates at any depth of the objects,
+/// the new metadata is kept if either side is not json
+pub fn union_metadata<C: ?Sized>(
+    _recorder: &C,
+    _attachs: &Attachments,
+    old: Vec<u8>,
+    new: Vec<u8>,
+) -> Option<Vec<u8>> {
+    JsonMerger::new(JsonMerge::Union).merge(MetadataCodec::Json, old, new)
+}
+
+/// the new metadata unless it is empty, or json null or an empty json string, array or object
+pub fn prefer_non_empty_metadata<C: ?Sized>(
+    _recorder: &C,
+    _attachs: &Attachments,
+    old: Vec<u8>,
+    new: Vec<u8>,
+) -> Option<Vec<u8>> {
+    let empty = new.is_empty()
+        || MetadataCodec::Json
+            .decode::<Value>(&new)
+            .map(|new| is_empty(&new))
+            .unwrap_or(false);
+    Some(if empty { old } else { new })
+}
@@ -0,0 +1,68 @@
+use super::*;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// serialization of `Record.metadata`, only json metadata can be matched by `MetadataFilter`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MetadataCodec {
+    #[default]
+    Json,
+    #[cfg(feature = "cbor")]
+    Cbor,
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+fn metadata_error<E: ToString>(e: E) -> ChatRecordError {
+    ChatRecordError::MetadataError(e.to_string())
+}
+
+impl MetadataCodec {
+    pub fn encode<T: Serialize>(&self, value: &T) -> ChatRecordResult<Vec<u8>> {
+        match self {
+            Self::Json => serde_json::to_vec(value).map_err(metadata_error),
+            #[cfg(feature = "cbor")]
+            Self::Cbor => {
+                let mut data = vec![];
+                ciborium::into_writer(value, &mut data).map_err(metadata_error)?;
+                Ok(data)
+            }
+            #[cfg(feature = "msgpack")]
+            Self::MessagePack => rmp_serde::to_vec_named(value).map_err(metadata_error),
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> ChatRecordResult<T> {
+        match self {
+            Self::Json => serde_json::from_slice(data).map_err(metadata_error),
+            #[cfg(feature = "cbor")]
+            Self::Cbor => ciborium::from_reader(data).map_err(metadata_error),
+            #[cfg(feature = "msgpack")]
+            Self::MessagePack => rmp_serde::from_slice(data).map_err(metadata_error),
+        }
+    }
+}
+
+/// json path filter on `Record.metadata` evaluated by sqlite json1, e.g. `$.reply.id`,
+/// records without json metadata have no value at any path,
+/// comparisons with a missing value never match, even if negated
+#[derive(Clone, Debug, PartialEq)]
+pub enum MetadataFilter {
+    /// a non-null value at the path
+    Exists(String),
+    Eq(String, Value),
+    /// compared in sqlite order, numbers are less than strings
+    Gt(String, Value),
+    Lt(String, Value),
+    In(String, Vec<Value>),
+    Not(Box<MetadataFilter>),
+}
+
+impl MetadataFilter {
+    pub fn negate(self) -> Self {
+        match self {
+            Self::Not(filter) => *filter,
+            filter => Self::Not(Box::new(filter)),
+        }
+    }
+}
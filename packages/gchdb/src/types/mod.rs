@@ -1,29 +1,60 @@
 mod attach;
 mod blob;
+mod conversation;
+mod cursor;
+mod embedding;
+mod entity;
 mod error;
+mod expr;
+mod facet;
+mod fingerprint;
+mod merger;
+mod metadata;
+mod person;
 mod query;
 mod record;
+mod search;
+mod sender;
+mod stats;
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::convert::TryInto;
 
 pub use crate::schema::*;
-pub use attach::Attachment;
+pub use attach::{Attachment, AttachmentHit, AttachmentPolicy, AttachmentReport, AttachmentText};
 pub use blob::Blob;
+pub use conversation::{Conversation, ConversationSummary, Participant};
+pub use cursor::{Cursor, CursorDirection, RecordPage};
+pub use embedding::{Embedder, Embedding, ScoredRecord};
+pub use entity::{Entities, EntityCount, EntityKind};
 pub use error::ChatRecordError;
-pub use query::Query;
-pub use record::Record;
+pub use expr::QueryExpr;
+pub use facet::{FacetCount, FacetCounts, FacetField, FacetedRecords};
+pub use fingerprint::{DuplicateCluster, DuplicateSpread, Fingerprint};
+pub use merger::{
+    deep_merge_metadata, keep_new_metadata, keep_old_metadata, prefer_non_empty_metadata,
+    union_metadata, JsonMerge, JsonMerger, MergedMetadata, MetadataMerger,
+};
+pub use metadata::{MetadataCodec, MetadataFilter};
+pub use person::{Person, PersonIdentity};
+pub use query::{Query, QuerySort, StringFilter};
+pub use record::{Record, RecordContext};
+pub use search::{MoreLikeThisOptions, SearchOperator, SearchRequest};
+pub use sender::{Sender, SenderAlias};
+pub use stats::{HistogramBucket, HistogramInterval, RecordStats, StatsCount, StatsGroup};
 
 pub type Attachments = HashMap<String, Vec<u8>>;
 
-pub type MetadataMerger<C> = fn(&C, &Attachments, Vec<u8>, Vec<u8>) -> Option<Vec<u8>>;
-
 pub trait ChatRecorder<'a> {
+    /// the merger is called if both the stored and the new record have metadata,
+    /// without a merger the new metadata replaces the old wholesale like `keep_new_metadata`,
+    /// pass `deep_merge_metadata` or a `JsonMerger` to keep the old keys
+    /// returns whether the record and all its attachments are stored
     fn insert_or_update_record<R>(
         &mut self,
         record: R,
-        merger: Option<MetadataMerger<Self>>,
+        merger: Option<&mut dyn MetadataMerger<Self>>,
     ) -> ChatRecordResult<bool>
     where
         R: Into<RecordType<'a>>;
@@ -101,21 +132,4 @@ impl<'a> From<(&'a Record, Attachments)> for RecordType<'a> {
     }
 }
 
-pub enum AttachType {
-    Id(i32),
-    Attach(Attachment),
-}
-
-impl From<i32> for AttachType {
-    fn from(src: i32) -> Self {
-        Self::Id(src)
-    }
-}
-
-impl From<Attachment> for AttachType {
-    fn from(src: Attachment) -> Self {
-        Self::Attach(src)
-    }
-}
-
 pub type ChatRecordResult<T> = Result<T, ChatRecordError>;
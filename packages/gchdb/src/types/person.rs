@@ -0,0 +1,25 @@
+use super::*;
+
+#[derive(Queryable, Insertable, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[diesel(table_name = persons)]
+pub struct Person {
+    pub id: Option<i32>,
+    pub name: String,
+    pub metadata: Option<Vec<u8>>,
+}
+
+impl Person {
+    pub fn get_id(&self) -> i32 {
+        self.id.unwrap_or_default()
+    }
+}
+
+/// a `(chat_type, sender_id)` identity, each identity belongs to at most one person
+#[derive(Queryable, Insertable, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[diesel(table_name = person_identities)]
+pub struct PersonIdentity {
+    pub id: Option<i32>,
+    pub person_id: i32,
+    pub chat_type: String,
+    pub sender_id: String,
+}
@@ -1,38 +1,149 @@
 use super::*;
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuerySort {
+    TimestampAsc,
+    TimestampDesc,
+    IdAsc,
+    IdDesc,
+    /// score of the keyword search, the same as the default order if there is no keyword
+    Relevance,
+}
+
+/// how a string field is matched, strings convert to `Exact`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StringFilter {
+    Exact(String),
+    /// `%` and `_` in the prefix are matched literally
+    Prefix(String),
+    /// sql LIKE pattern, `\` escapes `%`, `_` and itself
+    Like(String),
+    In(Vec<String>),
+    Not(Box<StringFilter>),
+}
+
+impl StringFilter {
+    pub fn any<I, S>(values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self::In(values.into_iter().map(Into::into).collect())
+    }
+
+    pub fn negate(self) -> Self {
+        match self {
+            Self::Not(filter) => *filter,
+            filter => Self::Not(Box::new(filter)),
+        }
+    }
+
+    /// escape `%`, `_` and `\` so the string is matched literally in a LIKE pattern
+    pub fn escape_like(src: &str) -> String {
+        let mut escaped = String::with_capacity(src.len());
+        for c in src.chars() {
+            if matches!(c, '%' | '_' | '\\') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        escaped
+    }
+}
+
+impl From<&str> for StringFilter {
+    fn from(src: &str) -> Self {
+        Self::Exact(src.into())
+    }
+}
+
+impl From<String> for StringFilter {
+    fn from(src: String) -> Self {
+        Self::Exact(src)
+    }
+}
+
+impl From<Vec<String>> for StringFilter {
+    fn from(src: Vec<String>) -> Self {
+        Self::In(src)
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct Query {
-    pub chat_type: Option<String>,
-    pub owner_id: Option<String>,
-    pub group_id: Option<String>,
-    pub sender_id: Option<String>,
-    pub sender_name: Option<String>,
-    pub keyword: Option<String>,
+    pub chat_type: Option<StringFilter>,
+    pub owner_id: Option<StringFilter>,
+    pub group_id: Option<StringFilter>,
+    pub sender_id: Option<StringFilter>,
+    pub sender_name: Option<StringFilter>,
+    /// only match records sent by any identity linked to the person
+    pub person_id: Option<i32>,
+    pub ids: Option<Vec<i32>>,
+    pub has_attachments: Option<bool>,
+    pub keyword: Option<SearchRequest>,
+    /// records containing all the entities, searched by the indexer with the keyword
+    pub entities: Vec<(EntityKind, String)>,
+    /// records matched by all the json path filters of the metadata
+    pub metadata: Vec<MetadataFilter>,
     pub before: Option<i64>,
     pub after: Option<i64>,
     pub offset: Option<u64>,
     pub limit: Option<u32>,
+    /// page by `(timestamp, id)` from the cursor instead of `offset`, ignored by keyword search
+    pub cursor: Option<Cursor>,
+    /// defaults to `TimestampDesc` for keyword search and `TimestampAsc` otherwise,
+    /// ties are always broken by id in the same direction, ignored if `cursor` is set
+    pub sort: Option<QuerySort>,
+    /// replace `sender_name` of the results with the latest name of the sender
+    pub resolve_sender_name: bool,
 }
 
 impl Query {
-    pub fn get_chat_type(&self) -> &str {
-        self.chat_type.as_deref().unwrap_or("%%")
-    }
-
-    pub fn get_owner_id(&self) -> &str {
-        self.owner_id.as_deref().unwrap_or("%%")
+    /// whether any filter other than the keyword is set
+    pub fn has_filters(&self) -> bool {
+        self.chat_type.is_some()
+            || self.owner_id.is_some()
+            || self.group_id.is_some()
+            || self.sender_id.is_some()
+            || self.sender_name.is_some()
+            || self.person_id.is_some()
+            || self.ids.is_some()
+            || self.has_attachments.is_some()
+            || !self.metadata.is_empty()
+            || self.before.is_some()
+            || self.after.is_some()
     }
 
-    pub fn get_group_id(&self) -> &str {
-        self.group_id.as_deref().unwrap_or("%%")
+    /// whether the keyword or any entity is set, so the indexer is searched
+    pub fn has_search(&self) -> bool {
+        self.keyword.is_some() || !self.entities.is_empty()
     }
 
-    pub fn get_sender_id(&self) -> &str {
-        self.sender_id.as_deref().unwrap_or("%%")
+    /// the keyword and the entities combined into one search of the indexer
+    pub fn get_search(&self) -> Option<SearchRequest> {
+        let mut requests = self
+            .keyword
+            .iter()
+            .cloned()
+            .chain(
+                self.entities
+                    .iter()
+                    .map(|(kind, value)| SearchRequest::Entity(*kind, value.clone())),
+            )
+            .collect::<Vec<_>>();
+        match requests.len() {
+            0 => None,
+            1 => requests.pop(),
+            _ => Some(SearchRequest::All(requests)),
+        }
     }
 
-    pub fn get_sender_name(&self) -> &str {
-        self.sender_name.as_deref().unwrap_or("%%")
+    pub fn get_sort(&self) -> QuerySort {
+        match (self.sort, self.has_search()) {
+            (Some(QuerySort::Relevance), false) | (None, false) => QuerySort::TimestampAsc,
+            (None, true) => QuerySort::TimestampDesc,
+            (Some(sort), _) => sort,
+        }
     }
 
     pub fn get_offset(&self) -> i64 {
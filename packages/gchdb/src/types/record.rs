@@ -1,4 +1,5 @@
 use super::*;
+use serde::de::DeserializeOwned;
 
 #[derive(Queryable, Insertable, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 #[diesel(table_name = records)]
@@ -18,6 +19,24 @@ impl Record {
     pub fn get_id(&self) -> i32 {
         self.id.unwrap_or_default()
     }
+    /// decode the metadata, none if the record has no metadata
+    pub fn get_metadata<T: DeserializeOwned>(
+        &self,
+        codec: MetadataCodec,
+    ) -> ChatRecordResult<Option<T>> {
+        self.metadata
+            .as_ref()
+            .map(|metadata| codec.decode(metadata))
+            .transpose()
+    }
+    pub fn set_metadata<T: Serialize>(
+        &mut self,
+        codec: MetadataCodec,
+        metadata: &T,
+    ) -> ChatRecordResult<()> {
+        self.metadata = Some(codec.encode(metadata)?);
+        Ok(())
+    }
     pub fn display(&self) -> String {
         format!(
             "{} ({}): {}",
@@ -25,3 +44,11 @@ impl Record {
         )
     }
 }
+
+/// a record with the records around it in the same conversation, in ascending order of time
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RecordContext {
+    pub record: Record,
+    pub before: Vec<Record>,
+    pub after: Vec<Record>,
+}
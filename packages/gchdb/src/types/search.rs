@@ -0,0 +1,110 @@
+use super::*;
+
+/// how the terms of `SearchRequest::Terms` are combined
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchOperator {
+    And,
+    Or,
+}
+
+/// typed full-text search, strings convert to `Query`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SearchRequest {
+    /// query parser syntax, fails on invalid syntax
+    Query(String),
+    /// query parser syntax, the input is escaped and searched as plain words if it fails to parse
+    Lenient(String),
+    /// tokenized text, the terms are combined by the operator
+    Terms {
+        text: String,
+        operator: SearchOperator,
+    },
+    /// the tokens of the text in order
+    Phrase(String),
+    /// a phrase whose last token is a prefix
+    Prefix(String),
+    /// each token within the edit distance, transpositions count as one edit
+    Fuzzy { text: String, distance: u8 },
+    /// regular expression matched against single terms of the index
+    Regex(String),
+    /// records containing the entity, the value is normalized by `EntityKind::normalize`
+    Entity(EntityKind, String),
+    /// records matched by all the requests
+    All(Vec<SearchRequest>),
+}
+
+impl SearchRequest {
+    pub fn all<S: Into<String>>(text: S) -> Self {
+        Self::Terms {
+            text: text.into(),
+            operator: SearchOperator::And,
+        }
+    }
+
+    pub fn any<S: Into<String>>(text: S) -> Self {
+        Self::Terms {
+            text: text.into(),
+            operator: SearchOperator::Or,
+        }
+    }
+
+    /// quote every word of the input, so the query parser matches it literally
+    pub fn escape(src: &str) -> String {
+        src.split_whitespace()
+            .map(|word| {
+                let mut escaped = String::with_capacity(word.len() + 2);
+                escaped.push('"');
+                for c in word.chars() {
+                    if matches!(c, '"' | '\\') {
+                        escaped.push('\\');
+                    }
+                    escaped.push(c);
+                }
+                escaped.push('"');
+                escaped
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl From<&str> for SearchRequest {
+    fn from(src: &str) -> Self {
+        Self::Query(src.into())
+    }
+}
+
+impl From<String> for SearchRequest {
+    fn from(src: String) -> Self {
+        Self::Query(src)
+    }
+}
+
+/// term selection of a more-like-this search, the defaults suit short chat messages
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MoreLikeThisOptions {
+    /// terms in fewer records are ignored
+    pub min_doc_frequency: u64,
+    /// terms in more records are ignored, e.g. to skip words used everywhere
+    pub max_doc_frequency: Option<u64>,
+    /// terms occurring fewer times in the source record are ignored
+    pub min_term_frequency: usize,
+    /// the most significant terms used in the query
+    pub max_query_terms: usize,
+    /// terms shorter than this in bytes are ignored
+    pub min_word_length: Option<usize>,
+    pub stop_words: Vec<String>,
+}
+
+impl Default for MoreLikeThisOptions {
+    fn default() -> Self {
+        Self {
+            min_doc_frequency: 1,
+            max_doc_frequency: None,
+            min_term_frequency: 1,
+            max_query_terms: 25,
+            min_word_length: None,
+            stop_words: vec![],
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use super::*;
+
+#[derive(Queryable, Insertable, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[diesel(table_name = senders)]
+pub struct Sender {
+    pub id: Option<i32>,
+    pub chat_type: String,
+    pub sender_id: String,
+    pub name: String,
+    pub timestamp: i64,
+}
+
+impl Sender {
+    pub fn get_id(&self) -> i32 {
+        self.id.unwrap_or_default()
+    }
+}
+
+/// a name used by the sender since `timestamp`, until the next alias of the same sender
+#[derive(Queryable, Insertable, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[diesel(table_name = sender_aliases)]
+pub struct SenderAlias {
+    pub id: Option<i32>,
+    pub chat_type: String,
+    pub sender_id: String,
+    pub name: String,
+    pub timestamp: i64,
+}
@@ -0,0 +1,96 @@
+use super::*;
+
+/// how records are grouped for counting,
+/// the time based groups take an utc offset in seconds and use the local time of that offset
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsGroup {
+    ChatType,
+    Owner,
+    Group,
+    Sender,
+    /// `YYYY-MM-DD`
+    Day(i32),
+    /// `YYYY-MM`
+    Month(i32),
+    /// hour of day, `00` to `23`
+    Hour(i32),
+    /// day of week, `0` is sunday
+    Weekday(i32),
+    /// day of week and hour of day, `0-00` to `6-23`
+    WeekdayHour(i32),
+}
+
+impl StatsGroup {
+    pub fn is_time(&self) -> bool {
+        matches!(
+            self,
+            Self::Day(_) | Self::Month(_) | Self::Hour(_) | Self::Weekday(_) | Self::WeekdayHour(_)
+        )
+    }
+
+    pub(crate) fn get_expression(&self) -> String {
+        let strftime = |format: &str, offset: &i32| {
+            format!(
+                "strftime('{}', timestamp / 1000 + {}, 'unixepoch')",
+                format, offset
+            )
+        };
+        match self {
+            Self::ChatType => "chat_type".into(),
+            Self::Owner => "owner_id".into(),
+            Self::Group => "group_id".into(),
+            Self::Sender => "sender_id".into(),
+            Self::Day(offset) => strftime("%Y-%m-%d", offset),
+            Self::Month(offset) => strftime("%Y-%m", offset),
+            Self::Hour(offset) => strftime("%H", offset),
+            Self::Weekday(offset) => strftime("%w", offset),
+            Self::WeekdayHour(offset) => strftime("%w-%H", offset),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct StatsCount {
+    pub key: String,
+    pub count: i64,
+    pub first: Option<i64>,
+    pub last: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RecordStats {
+    pub count: i64,
+    pub first: Option<i64>,
+    pub last: Option<i64>,
+    pub attachment_count: i64,
+    /// total size of attachments in bytes, a blob shared by several attachments is counted for each of them
+    pub attachment_size: i64,
+}
+
+/// bucket size of a date histogram, takes an utc offset in seconds like `StatsGroup`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistogramInterval {
+    Day(i32),
+    /// weeks start on monday
+    Week(i32),
+    Month(i32),
+}
+
+impl HistogramInterval {
+    pub(crate) const DAY: i64 = 86_400_000;
+
+    pub(crate) fn get_offset(&self) -> i32 {
+        match self {
+            Self::Day(offset) | Self::Week(offset) | Self::Month(offset) => *offset,
+        }
+    }
+}
+
+/// `key` is the local date the bucket starts on, `YYYY-MM-DD`, or `YYYY-MM` for months
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct HistogramBucket {
+    pub key: String,
+    /// utc timestamp in milliseconds of the start of the bucket
+    pub start: i64,
+    pub count: u64,
+}